@@ -6,6 +6,8 @@ static TARGET_PATH: &str = "../user/target/riscv64gc-unknown-none-elf/release/";
 fn main() {
     println!("cargo:rerun-if-changed=../user/src/");
     println!("cargo:rerun-if-changed={}", TARGET_PATH);
+    // build flag for syscall tracing from boot, see `build_trace_mask`
+    println!("cargo:rerun-if-env-changed=STRACE");
     insert_app_data().unwrap();
 }
 
@@ -46,8 +48,9 @@ _num_app:
     .section .data
     .global app_{0}_start
     .global app_{0}_end
+    .align 3
 app_{0}_start:
-    .incbin "{2}{1}.bin"
+    .incbin "{2}{1}"
 app_{0}_end:"#,
             idx, app, TARGET_PATH
         )?;
@@ -1,10 +1,14 @@
 pub const APP_BASE_ADDRESS: usize = 0x80400000;
 pub const APP_SIZE_LIMIT: usize = 0x20000;
-pub const MAX_APP_NUM: usize = 4;
+pub const MAX_APP_NUM: usize = 6;
+// keep in sync with the boot stacks and the hart id check in entry.asm
+pub const MAX_HARTS: usize = 4;
 pub const KERNEL_STACK_SIZE: usize = 4096 * 2;
 pub const KERNEL_HEAP_SIZE: usize = 0x30_0000;
 pub const USER_STACK_SIZE: usize = 4096 * 2;
 pub const CLOCK_FREQ: usize = 12500000;
+// covers the syscalls of Linux and the private ones from 1000 on
+pub const MAX_SYSCALL_NUM: usize = 1100;
 
 pub const PAGE_SIZE: usize = 0x1000;
 pub const PAGE_SIZE_BITS: usize = 0xc;
@@ -13,6 +17,11 @@ pub const VA_WIDTH_SV39: usize = 39;
 pub const PPN_WIDTH_SV39: usize = PA_WIDTH_SV39 - PAGE_SIZE_BITS;
 pub const VPN_WIDTH_SV39: usize = VA_WIDTH_SV39 - PAGE_SIZE_BITS;
 
+// mmio of the QEMU virt machine
+pub const PLIC_BASE: usize = 0x0c00_0000;
+pub const UART_BASE: usize = 0x1000_0000;
+pub const UART_IRQ: usize = 10;
+
 // setting whole physics memory end address
 pub const MEMORY_END: usize = 0x80800000;
 
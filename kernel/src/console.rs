@@ -1,19 +1,77 @@
 use crate::sbi::console_putchar;
+use crate::sync::{SpinLock, WaitQueue};
+use alloc::collections::VecDeque;
 use core::fmt::{Arguments, Write};
+use lazy_static::lazy_static;
+
+/// characters beyond it are dropped until readers catch up
+const INPUT_BUFFER_SIZE: usize = 256;
 
 struct Stdout;
 
 impl Write for Stdout {
     fn write_str(&mut self, s: &str) -> core::fmt::Result {
-        for c in s.chars() {
+        self.write_bytes(s.as_bytes());
+        Ok(())
+    }
+}
+
+impl Stdout {
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        for &c in bytes {
             console_putchar(c as usize);
         }
-        Ok(())
     }
 }
 
+/// keeps lines printed by different harts from interleaving
+static STDOUT: SpinLock<Stdout> = SpinLock::new(Stdout);
+
 pub fn print(args: Arguments) {
-    Stdout.write_fmt(args).unwrap();
+    STDOUT.lock().write_fmt(args).unwrap();
+}
+
+/// Print raw bytes, which need not be utf-8.
+pub fn write_bytes(bytes: &[u8]) {
+    STDOUT.lock().write_bytes(bytes);
+}
+
+lazy_static! {
+    /// characters received by the uart and not read yet
+    static ref INPUT_BUFFER: SpinLock<VecDeque<u8>> = SpinLock::new(VecDeque::new());
+    /// tasks blocked in `getchar` on an empty buffer
+    static ref INPUT_WAIT_QUEUE: WaitQueue = WaitQueue::new();
+}
+
+/// Called by the uart irq handler with a received character, wakes up a blocked reader.
+pub fn push_input(c: u8) {
+    let mut buffer = INPUT_BUFFER.lock();
+    if buffer.len() < INPUT_BUFFER_SIZE {
+        buffer.push_back(c);
+        INPUT_WAIT_QUEUE.wake_one();
+    }
+}
+
+/// Take a character of console input if there is any.
+///
+/// Input only comes through the uart irq, the sbi console is not polled as it would
+/// race with the irq handler for the receive register.
+pub fn try_getchar() -> Option<u8> {
+    INPUT_BUFFER.lock().pop_front()
+}
+
+/// Take a character of console input, block until one is received,
+/// `None` if a signal interrupts the wait.
+pub fn getchar() -> Option<u8> {
+    loop {
+        let mut buffer = INPUT_BUFFER.lock();
+        if let Some(c) = buffer.pop_front() {
+            return Some(c);
+        }
+        if !INPUT_WAIT_QUEUE.sleep_on_and_release(|| drop(buffer)) {
+            return None;
+        }
+    }
 }
 
 #[macro_export]
@@ -0,0 +1,50 @@
+mod plic;
+mod uart;
+
+use crate::config::{MAX_HARTS, PLIC_BASE, UART_IRQ};
+use crate::println;
+use crate::riscv::hart_id;
+use crate::sync::SpinLock;
+use alloc::collections::BTreeMap;
+use lazy_static::lazy_static;
+use plic::Plic;
+
+static PLIC: Plic = Plic::new(PLIC_BASE);
+
+lazy_static! {
+    /// handlers of external interrupts by irq number
+    static ref IRQ_HANDLERS: SpinLock<BTreeMap<usize, fn()>> = SpinLock::new(BTreeMap::new());
+}
+
+/// Route `irq` to `handler` and enable it on all harts, any hart may take it.
+pub fn register_irq(irq: usize, handler: fn()) {
+    IRQ_HANDLERS.lock().insert(irq, handler);
+    PLIC.set_priority(irq, 1);
+    for hart in 0..MAX_HARTS {
+        PLIC.set_enable(hart, irq, true);
+    }
+}
+
+/// Initialize devices, called once by the boot hart.
+pub fn init() {
+    register_irq(UART_IRQ, uart::handle_irq);
+    uart::init();
+}
+
+/// Let external interrupts of all priorities reach current hart.
+pub fn init_hart() {
+    PLIC.set_threshold(hart_id(), 0);
+}
+
+/// Handle a supervisor external interrupt, claim irqs from the plic until none is pending.
+pub fn handle_external_interrupt() {
+    let hart = hart_id();
+    while let Some(irq) = PLIC.claim(hart) {
+        let handler = IRQ_HANDLERS.lock().get(&irq).copied();
+        match handler {
+            Some(handler) => handler(),
+            None => println!("[kernel] Unhandled irq {}", irq),
+        }
+        PLIC.complete(hart, irq);
+    }
+}
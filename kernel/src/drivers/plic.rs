@@ -0,0 +1,64 @@
+//! Platform-level interrupt controller of the QEMU virt machine.
+
+use core::ptr::{read_volatile, write_volatile};
+
+/// Interrupt contexts of a hart, QEMU virt gives each hart an M-mode and an S-mode one.
+fn supervisor_context(hart_id: usize) -> usize {
+    hart_id * 2 + 1
+}
+
+pub struct Plic {
+    base: usize,
+}
+
+impl Plic {
+    pub const fn new(base: usize) -> Self {
+        Self { base }
+    }
+
+    /// Priority of `irq`, 0 disables it, an interrupt is delivered when above the threshold.
+    pub fn set_priority(&self, irq: usize, priority: u32) {
+        unsafe { write_volatile((self.base + irq * 4) as *mut u32, priority) }
+    }
+
+    /// Enable or disable `irq` for the S-mode context of `hart_id`.
+    pub fn set_enable(&self, hart_id: usize, irq: usize, enable: bool) {
+        let context = supervisor_context(hart_id);
+        let reg = (self.base + 0x2000 + context * 0x80 + irq / 32 * 4) as *mut u32;
+        unsafe {
+            let bits = read_volatile(reg);
+            let mask = 1 << (irq % 32);
+            write_volatile(reg, if enable { bits | mask } else { bits & !mask });
+        }
+    }
+
+    /// Interrupts of priority not above `threshold` are masked for the S-mode context of `hart_id`.
+    pub fn set_threshold(&self, hart_id: usize, threshold: u32) {
+        let context = supervisor_context(hart_id);
+        unsafe {
+            write_volatile(
+                (self.base + 0x20_0000 + context * 0x1000) as *mut u32,
+                threshold,
+            )
+        }
+    }
+
+    fn claim_complete_reg(&self, hart_id: usize) -> *mut u32 {
+        let context = supervisor_context(hart_id);
+        (self.base + 0x20_0004 + context * 0x1000) as *mut u32
+    }
+
+    /// Take the highest-priority pending irq of the S-mode context of `hart_id`,
+    /// `None` if another hart has claimed it already.
+    pub fn claim(&self, hart_id: usize) -> Option<usize> {
+        match unsafe { read_volatile(self.claim_complete_reg(hart_id)) } {
+            0 => None,
+            irq => Some(irq as usize),
+        }
+    }
+
+    /// Tell the plic `irq` claimed by `hart_id` has been handled.
+    pub fn complete(&self, hart_id: usize, irq: usize) {
+        unsafe { write_volatile(self.claim_complete_reg(hart_id), irq as u32) }
+    }
+}
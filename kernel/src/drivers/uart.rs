@@ -0,0 +1,35 @@
+//! Receive side of the ns16550a uart, output still goes through sbi.
+
+use crate::config::UART_BASE;
+use crate::console::push_input;
+use core::ptr::{read_volatile, write_volatile};
+
+/// receiver buffer register
+const RBR: usize = 0;
+/// interrupt enable register
+const IER: usize = 1;
+/// line status register
+const LSR: usize = 5;
+
+const IER_RX_AVAILABLE: u8 = 1 << 0;
+const LSR_DATA_READY: u8 = 1 << 0;
+
+fn read_reg(offset: usize) -> u8 {
+    unsafe { read_volatile((UART_BASE + offset) as *const u8) }
+}
+
+fn write_reg(offset: usize, value: u8) {
+    unsafe { write_volatile((UART_BASE + offset) as *mut u8, value) }
+}
+
+/// Raise an interrupt whenever a character is received.
+pub fn init() {
+    write_reg(IER, read_reg(IER) | IER_RX_AVAILABLE);
+}
+
+/// Irq handler, move all received characters into the console input buffer.
+pub fn handle_irq() {
+    while read_reg(LSR) & LSR_DATA_READY != 0 {
+        push_input(read_reg(RBR));
+    }
+}
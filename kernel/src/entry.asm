@@ -1,12 +1,39 @@
     .section .text.entry
     .global _start
 _start:
-    la sp, boot_stack_top
+    # a0 = hart id, kept in tp while running in kernel
+    # harts from MAX_HARTS (4, see config.rs) on have no boot stack and no per-hart state
+    li t0, 4
+    bgeu a0, t0, park
+    mv tp, a0
+    # sp = boot_stack + (hart id + 1) * 64 KiB
+    addi t0, a0, 1
+    slli t0, t0, 16
+    la sp, boot_stack
+    add sp, sp, t0
     call rust_main
 
+    .global _start_secondary
+_start_secondary:
+    # entry of harts started by the boot hart through sbi hsm, a0 = hart id
+    li t0, 4
+    bgeu a0, t0, park
+    mv tp, a0
+    addi t0, a0, 1
+    slli t0, t0, 16
+    la sp, boot_stack
+    add sp, sp, t0
+    call rust_main_secondary
+
+park:
+    # never used by the kernel, wait here forever
+    wfi
+    j park
+
     .section .bss.stack
     .global boot_stack
 boot_stack:
-    .space 4096 * 16
+    # a 64 KiB boot stack for each of MAX_HARTS harts
+    .space 4096 * 16 * 4
     .global boot_stack_top
-boot_stack_top:
\ No newline at end of file
+boot_stack_top:
@@ -0,0 +1,17 @@
+mod stdio;
+
+use crate::syscall::SysResult;
+
+pub use stdio::{Stderr, Stdin, Stdout};
+
+/// An open file of a process, e.g. the console, shared by its fds with `Arc`.
+///
+/// Buffers are user memory, which is identical to physical memory as paging is not on.
+pub trait File: Send + Sync {
+    fn readable(&self) -> bool;
+    fn writable(&self) -> bool;
+    /// Read into `buf` and return how many bytes were read.
+    fn read(&self, buf: &mut [u8]) -> SysResult;
+    /// Write from `buf` and return how many bytes were written.
+    fn write(&self, buf: &[u8]) -> SysResult;
+}
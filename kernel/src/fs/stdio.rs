@@ -0,0 +1,86 @@
+use super::File;
+use crate::console::{getchar, try_getchar, write_bytes};
+use crate::syscall::{SysError, SysResult};
+
+/// Console input, fd 0 of every process.
+pub struct Stdin;
+
+/// Console output, fd 1 of every process.
+pub struct Stdout;
+
+/// Console output as well, fd 2 of every process.
+pub struct Stderr;
+
+impl File for Stdin {
+    fn readable(&self) -> bool {
+        true
+    }
+
+    fn writable(&self) -> bool {
+        false
+    }
+
+    /// Block until some input arrives, then take what has arrived without blocking again,
+    /// `EINTR` if a signal comes first.
+    fn read(&self, buf: &mut [u8]) -> SysResult {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        buf[0] = getchar().ok_or(SysError::EINTR)?;
+        let mut count = 1;
+        while count < buf.len() {
+            match try_getchar() {
+                Some(c) => buf[count] = c,
+                None => break,
+            }
+            count += 1;
+        }
+        Ok(count as isize)
+    }
+
+    fn write(&self, _buf: &[u8]) -> SysResult {
+        Err(SysError::EBADF)
+    }
+}
+
+/// Write raw bytes to the console like a terminal, they need not be utf-8.
+fn write_console(buf: &[u8]) -> SysResult {
+    write_bytes(buf);
+    Ok(buf.len() as isize)
+}
+
+impl File for Stdout {
+    fn readable(&self) -> bool {
+        false
+    }
+
+    fn writable(&self) -> bool {
+        true
+    }
+
+    fn read(&self, _buf: &mut [u8]) -> SysResult {
+        Err(SysError::EBADF)
+    }
+
+    fn write(&self, buf: &[u8]) -> SysResult {
+        write_console(buf)
+    }
+}
+
+impl File for Stderr {
+    fn readable(&self) -> bool {
+        false
+    }
+
+    fn writable(&self) -> bool {
+        true
+    }
+
+    fn read(&self, _buf: &mut [u8]) -> SysResult {
+        Err(SysError::EBADF)
+    }
+
+    fn write(&self, buf: &[u8]) -> SysResult {
+        write_console(buf)
+    }
+}
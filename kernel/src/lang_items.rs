@@ -19,5 +19,5 @@ fn panic(info: &PanicInfo) -> ! {
     //     print_stack_track();
     // }
 
-    shutdown()
+    shutdown(true)
 }
@@ -1,8 +1,44 @@
 use crate::config::*;
 use crate::println;
+use crate::timer::get_time;
 use crate::trap::TrapContext;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
 use core::arch::asm;
+use core::ops::Range;
 use core::slice::from_raw_parts;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use xmas_elf::header;
+use xmas_elf::program::{ProgramHeader, Type};
+use xmas_elf::ElfFile;
+
+// auxiliary vector entries of the Linux initial stack
+const AT_NULL: usize = 0;
+const AT_PHDR: usize = 3;
+const AT_PHENT: usize = 4;
+const AT_PHNUM: usize = 5;
+const AT_PAGESZ: usize = 6;
+const AT_ENTRY: usize = 9;
+const AT_UID: usize = 11;
+const AT_EUID: usize = 12;
+const AT_GID: usize = 13;
+const AT_EGID: usize = 14;
+const AT_SECURE: usize = 23;
+const AT_RANDOM: usize = 25;
+
+// dynamic section tags and relocation types of position independent apps
+const DT_NULL: u64 = 0;
+const DT_RELA: u64 = 7;
+const DT_RELASZ: u64 = 8;
+const DT_RELAENT: u64 = 9;
+const R_RISCV_NONE: u64 = 0;
+const R_RISCV_RELATIVE: u64 = 3;
+const RELA_ENTRY_SIZE: usize = 24;
+
+/// Bit i is set if app i has been loaded, the others are never run.
+static LOADED_APPS: AtomicUsize = AtomicUsize::new(0);
 
 #[repr(align(4096))]
 #[derive(Copy, Clone)]
@@ -49,6 +85,16 @@ fn get_base_i(app_id: usize) -> usize {
     APP_BASE_ADDRESS + app_id * APP_SIZE_LIMIT
 }
 
+/// Memory of app `app_id`: its slot, and the user stack of its main thread.
+pub fn get_app_regions(app_id: usize) -> [Range<usize>; 2] {
+    let base = get_base_i(app_id);
+    let stack_top = USER_STACK[app_id].get_sp();
+    [
+        base..base + APP_SIZE_LIMIT,
+        stack_top - USER_STACK_SIZE..stack_top,
+    ]
+}
+
 /// Get the total number of applications.
 pub fn get_num_app() -> usize {
     extern "C" {
@@ -76,44 +122,233 @@ pub fn get_app_data(app_id: usize) -> &'static [u8] {
     }
 }
 
+/// ELF file of app `app_id`.
+fn get_app_elf(app_id: usize) -> ElfFile<'static> {
+    ElfFile::new(get_app_data(app_id)).expect("App is not an ELF file!")
+}
+
+/// Segments of `elf` to load.
+fn load_segments<'a>(elf: &'a ElfFile) -> impl Iterator<Item = ProgramHeader<'a>> + 'a {
+    elf.program_iter()
+        .filter(|ph| ph.get_type() == Ok(Type::Load))
+}
+
+/// Addresses the loaded segments of `elf` are linked at, `.bss` included,
+/// `None` if there is nothing to load.
+///
+/// The image is moved as a whole into the slot of the app and relocated there, since paging
+/// is off and the slot is not where the app is linked.
+fn image_range(elf: &ElfFile) -> Option<Range<usize>> {
+    let start = load_segments(elf)
+        .map(|ph| ph.virtual_addr() as usize)
+        .min()?;
+    let end = load_segments(elf)
+        .map(|ph| (ph.virtual_addr() + ph.mem_size()) as usize)
+        .max()?;
+    Some(start..end)
+}
+
+/// Whether app `app_id` has been loaded by `load_apps`.
+pub fn app_loaded(app_id: usize) -> bool {
+    app_id < MAX_APP_NUM && LOADED_APPS.load(Ordering::Acquire) & (1 << app_id) != 0
+}
+
 /// Load nth user app at
 /// [APP_BASE_ADDRESS + n * APP_SIZE_LIMIT, APP_BASE_ADDRESS + (n+1) * APP_SIZE_LIMIT).
+///
+/// Apps that can not be loaded are reported and skipped, they exit without running.
 pub fn load_apps() {
-    extern "C" {
-        fn _num_app();
-    }
-    let num_app_ptr = _num_app as usize as *const usize;
     let num_app = get_num_app();
-    let app_start = unsafe { core::slice::from_raw_parts(num_app_ptr.add(1), num_app + 1) };
     // clear i-cache first
     unsafe {
         asm!("fence.i");
     }
     // load apps
     for i in 0..num_app {
-        let base_i = get_base_i(i);
-        // clear region
-        (base_i..base_i + APP_SIZE_LIMIT)
-            .for_each(|addr| unsafe { (addr as *mut u8).write_volatile(0) });
-        // load app from data section to memory
-        let src = unsafe {
-            core::slice::from_raw_parts(app_start[i] as *const u8, app_start[i + 1] - app_start[i])
-        };
-        let dst = unsafe { core::slice::from_raw_parts_mut(base_i as *mut u8, src.len()) };
-        dst.copy_from_slice(src);
-        println!(
-            "load app {} address {:#x} size {}",
-            i,
-            dst.as_ptr() as usize,
-            dst.len()
-        );
+        match load_app(i) {
+            Ok(size) => {
+                LOADED_APPS.fetch_or(1 << i, Ordering::Release);
+                println!("load app {} address {:#x} size {}", i, get_base_i(i), size);
+            }
+            Err(err) => println!("[kernel] app {} not loaded: {}", i, err),
+        }
     }
 }
 
+/// Copy the segments of app `app_id` into its slot and relocate them, return the image size.
+///
+/// Only position independent executables (`ET_DYN`) linked statically can be loaded, such as
+/// the apps in `user` and musl programs built with `-static-pie`.
+fn load_app(app_id: usize) -> Result<usize, String> {
+    if app_id >= MAX_APP_NUM {
+        return Err(format!("only {} apps are supported", MAX_APP_NUM));
+    }
+    let elf = ElfFile::new(get_app_data(app_id))?;
+    if elf.header.pt2.type_().as_type() != header::Type::SharedObject {
+        return Err("not a position independent executable".into());
+    }
+    let image = image_range(&elf).ok_or("nothing to load")?;
+    if image.len() > APP_SIZE_LIMIT {
+        return Err(format!(
+            "{} bytes do not fit in a slot of {} bytes",
+            image.len(),
+            APP_SIZE_LIMIT
+        ));
+    }
+    let base_i = get_base_i(app_id);
+    // clear region, which also zeroes `.bss`
+    (base_i..base_i + APP_SIZE_LIMIT)
+        .for_each(|addr| unsafe { (addr as *mut u8).write_volatile(0) });
+    // load segments of the app from data section to memory
+    for ph in load_segments(&elf) {
+        if ph.file_size() > ph.mem_size() {
+            return Err("segment larger in file than in memory".into());
+        }
+        let src = elf
+            .input
+            .get(ph.offset() as usize..(ph.offset() + ph.file_size()) as usize)
+            .ok_or("segment out of file")?;
+        let dst = base_i + ph.virtual_addr() as usize - image.start;
+        unsafe { core::slice::from_raw_parts_mut(dst as *mut u8, src.len()) }.copy_from_slice(src);
+    }
+    relocate(&elf, &image, base_i)?;
+    Ok(image.len())
+}
+
+/// Apply the relocations of `elf`, whose `image` has been copied to `base`.
+///
+/// Statically linked apps only have `R_RISCV_RELATIVE` ones, adding the load bias to the
+/// absolute addresses in `.got` and `.data`.
+fn relocate(elf: &ElfFile, image: &Range<usize>, base: usize) -> Result<(), &'static str> {
+    let dynamic = match elf
+        .program_iter()
+        .find(|ph| ph.get_type() == Ok(Type::Dynamic))
+    {
+        Some(ph) => ph,
+        None => return Ok(()),
+    };
+    let read_u64 = |bytes: &[u8]| u64::from_le_bytes(bytes.try_into().unwrap());
+    let entries = elf
+        .input
+        .get(dynamic.offset() as usize..(dynamic.offset() + dynamic.file_size()) as usize)
+        .ok_or("dynamic segment out of file")?;
+    let (mut rela, mut rela_size, mut rela_ent) = (0, 0, RELA_ENTRY_SIZE);
+    for entry in entries.chunks_exact(16) {
+        let value = read_u64(&entry[8..]) as usize;
+        match read_u64(&entry[..8]) {
+            DT_NULL => break,
+            DT_RELA => rela = value,
+            DT_RELASZ => rela_size = value,
+            DT_RELAENT => rela_ent = value,
+            _ => {}
+        }
+    }
+    if rela_size == 0 {
+        return Ok(());
+    }
+    if rela_ent < RELA_ENTRY_SIZE {
+        return Err("bad relocation entry size");
+    }
+    // loaded address of `len` bytes linked at `va`, which must be inside the image
+    let loaded = |va: usize, len: usize| match va.checked_add(len) {
+        Some(end) if va >= image.start && end <= image.end => Ok(base + va - image.start),
+        _ => Err("relocation out of image"),
+    };
+    let bias = base.wrapping_sub(image.start) as u64;
+    let table = loaded(rela, rela_size)?;
+    for i in 0..rela_size / rela_ent {
+        let entry = unsafe { from_raw_parts((table + i * rela_ent) as *const u8, RELA_ENTRY_SIZE) };
+        let offset = read_u64(&entry[..8]) as usize;
+        let addend = read_u64(&entry[16..]);
+        match read_u64(&entry[8..16]) & 0xffff_ffff {
+            R_RISCV_NONE => {}
+            R_RISCV_RELATIVE => {
+                let target = loaded(offset, 8)?;
+                unsafe { (target as *mut u64).write_unaligned(bias.wrapping_add(addend)) };
+            }
+            _ => return Err("unsupported relocation, only static PIE can be loaded"),
+        }
+    }
+    Ok(())
+}
+
+/// Free memory of app `app_id` for its heap and anonymous mappings,
+/// i.e. the rest of its slot after the image and its `.bss`, page aligned.
+pub fn get_app_free_range(app_id: usize) -> (usize, usize) {
+    let base = get_base_i(app_id);
+    let image_end = base + image_range(&get_app_elf(app_id)).unwrap().len();
+    let start = (image_end + PAGE_SIZE - 1) & !(PAGE_SIZE - 1);
+    (start, base + APP_SIZE_LIMIT)
+}
+
 /// get app info with entry and sp and save `TrapContext` in kernel stack
 pub fn init_app_cx(app_id: usize) -> usize {
-    KERNEL_STACK[app_id].push_context(TrapContext::app_init_context(
-        get_base_i(app_id),
-        USER_STACK[app_id].get_sp(),
-    ))
+    let elf = get_app_elf(app_id);
+    let image = image_range(&elf).unwrap();
+    let base = get_base_i(app_id);
+    let relocate = |va: usize| base + va - image.start;
+    let entry = relocate(elf.header.pt2.entry_point() as usize);
+    let mut auxv = vec![
+        (AT_PHENT, elf.header.pt2.ph_entry_size() as usize),
+        (AT_PHNUM, elf.header.pt2.ph_count() as usize),
+        (AT_ENTRY, entry),
+        // musl probes the standard fds with ppoll if it can not tell the app is not setuid
+        (AT_UID, 0),
+        (AT_EUID, 0),
+        (AT_GID, 0),
+        (AT_EGID, 0),
+        (AT_SECURE, 0),
+    ];
+    // libc finds TLS in the program headers, which are in the segment loaded from offset 0
+    if let Some(ph) = load_segments(&elf).find(|ph| ph.offset() == 0) {
+        let phdr = ph.virtual_addr() as usize + elf.header.pt2.ph_offset() as usize;
+        auxv.push((AT_PHDR, relocate(phdr)));
+    }
+    let name = format!("app{}", app_id);
+    let sp = push_initial_stack(USER_STACK[app_id].get_sp(), &[&name], &[], &auxv);
+    KERNEL_STACK[app_id].push_context(TrapContext::app_init_context(entry, sp))
+}
+
+/// Build the Linux initial stack below user address `sp` and return the new sp, which points at
+/// argc, followed by argv, NULL, envp, NULL, and the auxv pairs of `auxv`, `AT_PAGESZ` and
+/// `AT_RANDOM` ending with `AT_NULL`; the strings and random bytes are above them.
+fn push_initial_stack(sp: usize, argv: &[&str], envp: &[&str], auxv: &[(usize, usize)]) -> usize {
+    // user addresses are physical ones as paging is off
+    let write_bytes = |va: usize, bytes: &[u8]| {
+        unsafe { core::slice::from_raw_parts_mut(va as *mut u8, bytes.len()) }
+            .copy_from_slice(bytes);
+    };
+    let mut sp = sp;
+    let mut push_str = |s: &str| {
+        sp -= s.len() + 1;
+        write_bytes(sp, s.as_bytes());
+        write_bytes(sp + s.len(), &[0]);
+        sp
+    };
+    let envp_ptrs: Vec<usize> = envp.iter().map(|s| push_str(s)).collect();
+    let argv_ptrs: Vec<usize> = argv.iter().map(|s| push_str(s)).collect();
+    // not cryptographically random, only used to seed stack canaries and hashing in libc
+    sp -= 16;
+    let random = sp;
+    let seed = get_time() as u64 ^ 0x9e37_79b9_7f4a_7c15;
+    write_bytes(random, &seed.to_ne_bytes());
+    write_bytes(random + 8, &seed.rotate_left(32).to_ne_bytes());
+
+    let mut words = vec![argv.len()];
+    words.extend(argv_ptrs);
+    words.push(0);
+    words.extend(envp_ptrs);
+    words.push(0);
+    for (key, value) in
+        auxv.iter()
+            .chain(&[(AT_PAGESZ, PAGE_SIZE), (AT_RANDOM, random), (AT_NULL, 0)])
+    {
+        words.extend([*key, *value]);
+    }
+    // argc must be 16-byte aligned
+    sp = (sp - words.len() * core::mem::size_of::<usize>()) & !0xf;
+    for (i, word) in words.iter().enumerate() {
+        write_bytes(sp + i * core::mem::size_of::<usize>(), &word.to_ne_bytes());
+    }
+    sp
 }
@@ -3,6 +3,7 @@
 #![feature(panic_info_message)]
 #![feature(alloc_error_handler)]
 
+use config::MAX_HARTS;
 use core::arch::global_asm;
 
 extern crate alloc;
@@ -11,6 +12,8 @@ extern crate bitflags;
 #[macro_use]
 mod config;
 mod console;
+mod drivers;
+mod fs;
 mod lang_items;
 mod loader;
 mod log;
@@ -28,16 +31,47 @@ global_asm!(include_str!("entry.asm"));
 global_asm!(include_str!("link_app.S"));
 
 #[no_mangle]
-pub fn rust_main() -> ! {
+pub fn rust_main(hart_id: usize) -> ! {
     clear_bss();
-    println!("[kernel] Hello, Kylin!");
+    println!("[kernel] Hello, Kylin! Boot hart {}", hart_id);
+    mm::init_heap();
     trap::init();
     loader::load_apps();
+    task::kthread_self_test();
+    drivers::init();
+    drivers::init_hart();
     trap::enable_timer_interrupt();
+    trap::enable_software_interrupt();
+    trap::enable_external_interrupt();
     timer::set_next_trigger();
+    start_other_harts(hart_id);
     println!("[kernel] Start to run applications!");
-    task::run_first_task();
-    panic!("Unreachable in rust_main!")
+    task::run_tasks()
+}
+
+/// Entry of secondary harts, started after the boot hart has initialized the kernel.
+#[no_mangle]
+pub fn rust_main_secondary(hart_id: usize) -> ! {
+    println!("[kernel] Hart {} started", hart_id);
+    trap::init();
+    drivers::init_hart();
+    trap::enable_timer_interrupt();
+    trap::enable_software_interrupt();
+    trap::enable_external_interrupt();
+    timer::set_next_trigger();
+    task::run_tasks()
+}
+
+/// Start all other harts through sbi hsm, harts which do not exist fail to start.
+///
+/// Harts from `MAX_HARTS` on are never started, they would park in `entry.asm` anyway.
+fn start_other_harts(boot_hart: usize) {
+    extern "C" {
+        fn _start_secondary();
+    }
+    for hart_id in (0..MAX_HARTS).filter(|&hart_id| hart_id != boot_hart) {
+        sbi::hart_start(hart_id, _start_secondary as usize, 0);
+    }
 }
 
 fn clear_bss() {
@@ -1,6 +1,8 @@
 use crate::config::MEMORY_END;
 use crate::mm::address::{PhysicAddress, PhysicPageNum};
-use crate::sync::UPSafeCell;
+use crate::sync::SpinLock;
+use crate::task::{current_page_counter, PageCounter};
+use alloc::sync::Arc;
 use alloc::vec::Vec;
 use lazy_static::lazy_static;
 
@@ -73,8 +75,8 @@ impl StackFrameAllocator {
 type FrameAllocatorImpl = StackFrameAllocator;
 
 lazy_static! {
-    pub static ref FRAME_ALLOCATOR: UPSafeCell<FrameAllocatorImpl> =
-        unsafe { UPSafeCell::new(FrameAllocatorImpl::new()) };
+    pub static ref FRAME_ALLOCATOR: SpinLock<FrameAllocatorImpl> =
+        SpinLock::new(FrameAllocatorImpl::new());
 }
 
 // 初始化页帧管理器
@@ -88,36 +90,55 @@ pub fn init_frame_allocator() {
     let end = PhysicAddress::from(MEMORY_END).floor();
 
     // 物理页帧全局管理器初始化
-    FRAME_ALLOCATOR.exclusive_access().init(start, end);
+    FRAME_ALLOCATOR.lock().init(start, end);
 }
 
 pub struct FrameTracker {
     pub ppn: PhysicPageNum,
+    // 页帧计入的进程页数，空闲循环分配的页帧不计入
+    owner: Option<Arc<PageCounter>>,
 }
 
 impl FrameTracker {
-    pub fn new(ppn: PhysicPageNum) -> Self {
+    pub fn new(ppn: PhysicPageNum, owner: Option<Arc<PageCounter>>) -> Self {
         let bytes_array = ppn.get_bytes_array();
         for i in bytes_array {
             *i = 0;
         }
-        Self { ppn }
+        Self { ppn, owner }
     }
 }
 
 impl Drop for FrameTracker {
     fn drop(&mut self) {
         frame_dealloc(self.ppn);
+        // 计数为原子量，持有任务管理器锁时释放页帧也不会死锁
+        if let Some(owner) = &self.owner {
+            owner.uncharge(1);
+        }
     }
 }
 
+// 分配的页帧计入当前进程，超出其 RLIMIT_AS 时分配失败
 pub fn frame_alloc() -> Option<FrameTracker> {
-    FRAME_ALLOCATOR
-        .exclusive_access()
-        .alloc()
-        .map(|ppn| FrameTracker::new(ppn))
+    let owner = current_page_counter();
+    if let Some(owner) = &owner {
+        if !owner.charge(1) {
+            return None;
+        }
+    }
+    let ppn = FRAME_ALLOCATOR.lock().alloc();
+    match ppn {
+        Some(ppn) => Some(FrameTracker::new(ppn, owner)),
+        None => {
+            if let Some(owner) = &owner {
+                owner.uncharge(1);
+            }
+            None
+        }
+    }
 }
 
 pub fn frame_dealloc(ppn: PhysicPageNum) {
-    FRAME_ALLOCATOR.exclusive_access().dealloc(ppn);
+    FRAME_ALLOCATOR.lock().dealloc(ppn);
 }
@@ -1,4 +1,12 @@
+use crate::config::PAGE_SIZE;
 use crate::mm::memory_set::KERNEL_SPACE;
+use crate::task::current_owns_user_range;
+use address::{PhysicAddress, VirtualAddress};
+use alloc::vec::Vec;
+use core::mem::{size_of, MaybeUninit};
+use core::slice;
+use page_table::{PTEFlags, PageTable};
+use riscv::register::satp::{self, Mode};
 
 mod address;
 mod frame_allocator;
@@ -6,8 +14,70 @@ mod heap_allocator;
 mod memory_set;
 mod page_table;
 
+pub use heap_allocator::init_heap;
+
+/// 通过当前页表将用户虚拟地址转换为物理地址, 用户态不能读取（write 时不能写入）该地址时返回 None
+///
+/// 未开启分页时二者相同, 只允许访问当前进程自己的内存: 应用所在区域与各线程的用户栈
+pub fn translate_user_addr(va: usize, write: bool) -> Option<usize> {
+    let satp = satp::read();
+    if satp.mode() == Mode::Bare {
+        return current_owns_user_range(va, va.checked_add(1)?).then_some(va);
+    }
+    let va = VirtualAddress(va);
+    let pte = PageTable::from_token(satp.bits()).translate(va.floor())?;
+    let access = if write { PTEFlags::W } else { PTEFlags::R };
+    if !pte.flags().contains(PTEFlags::V | PTEFlags::U | access) {
+        return None;
+    }
+    Some(PhysicAddress::from(pte.ppn()).0 + va.page_offset())
+}
+
+/// 将用户缓冲区 [ptr, ptr + len) 按页切分为内核可以直接访问的切片, 有一页不可访问时返回 None
+///
+/// 未开启分页时进程的各内存区域也按页对齐, 因此每页只需检查一个地址
+pub fn translated_byte_buffer(
+    ptr: usize,
+    len: usize,
+    write: bool,
+) -> Option<Vec<&'static mut [u8]>> {
+    let end = ptr.checked_add(len)?;
+    let mut buffers = Vec::new();
+    let mut start = ptr;
+    while start < end {
+        let chunk_end = ((start & !(PAGE_SIZE - 1)) + PAGE_SIZE).min(end);
+        let pa = translate_user_addr(start, write)?;
+        buffers.push(unsafe { slice::from_raw_parts_mut(pa as *mut u8, chunk_end - start) });
+        start = chunk_end;
+    }
+    Some(buffers)
+}
+
+/// 从用户地址 ptr 处读取一个 T, 不要求对齐, 不可读时返回 None
+pub fn read_user<T: Copy>(ptr: usize) -> Option<T> {
+    let mut value = MaybeUninit::<T>::uninit();
+    let bytes = unsafe { slice::from_raw_parts_mut(value.as_mut_ptr() as *mut u8, size_of::<T>()) };
+    let mut copied = 0;
+    for buffer in translated_byte_buffer(ptr, size_of::<T>(), false)? {
+        bytes[copied..copied + buffer.len()].copy_from_slice(buffer);
+        copied += buffer.len();
+    }
+    Some(unsafe { value.assume_init() })
+}
+
+/// 将 value 写入用户地址 ptr 处, 不要求对齐, 不可写时返回 None
+pub fn write_user<T: Copy>(ptr: usize, value: &T) -> Option<()> {
+    let bytes = unsafe { slice::from_raw_parts(value as *const T as *const u8, size_of::<T>()) };
+    let mut copied = 0;
+    for buffer in translated_byte_buffer(ptr, size_of::<T>(), true)? {
+        buffer.copy_from_slice(&bytes[copied..copied + buffer.len()]);
+        copied += buffer.len();
+    }
+    Some(())
+}
+
 pub fn init() {
     heap_allocator::init_heap();
     frame_allocator::init_frame_allocator();
-    KERNEL_SPACE.exclusive_access().activate();
+    KERNEL_SPACE.lock().activate();
 }
@@ -1,5 +1,5 @@
 use crate::config::PPN_WIDTH_SV39;
-use crate::mm::address::{PhysicPageNum, VirtualPageNum};
+use crate::mm::address::{PhysicAddress, PhysicPageNum, VirtualAddress, VirtualPageNum};
 use crate::mm::frame_allocator::{frame_alloc, FrameTracker};
 use alloc::vec;
 use alloc::vec::Vec;
@@ -134,4 +134,11 @@ impl PageTable {
     pub fn translate(&self, vpn: VirtualPageNum) -> Option<PageTableEntry> {
         self.find_pte(vpn).map(|pte| *pte)
     }
+
+    /// 虚拟地址转换为物理地址, 未映射时返回 None
+    pub fn translate_va(&self, va: VirtualAddress) -> Option<PhysicAddress> {
+        self.translate(va.floor())
+            .filter(|pte| pte.is_valid())
+            .map(|pte| PhysicAddress(PhysicAddress::from(pte.ppn()).0 + va.page_offset()))
+    }
 }
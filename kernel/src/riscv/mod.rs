@@ -6,3 +6,33 @@ pub fn sfence_vma() {
         asm!("sfence.vma");
     }
 }
+
+/// id of current hart, kept in `tp` since boot
+pub fn hart_id() -> usize {
+    let hart_id;
+    unsafe {
+        asm!("mv {}, tp", out(reg) hart_id);
+    }
+    hart_id
+}
+
+/// clear pending supervisor software interrupt, i.e. acknowledge an ipi
+pub fn clear_ssoft() {
+    unsafe {
+        asm!("csrc sip, {}", in(reg) 1 << 1);
+    }
+}
+
+/// set `sstatus.FS` of current hart to `Clean`, so the kernel may access fp registers
+pub fn enable_fp() {
+    unsafe {
+        asm!("csrc sstatus, {}", in(reg) 3 << 13);
+        asm!("csrs sstatus, {}", in(reg) 2 << 13);
+    }
+}
+
+pub fn wfi() {
+    unsafe {
+        asm!("wfi");
+    }
+}
@@ -12,6 +12,17 @@ const SBI_REMOTE_SFENCE_VMA: usize = 6;
 const SBI_REMOTE_SFENCE_VMA_ASID: usize = 7;
 const SBI_SHUTDOWN: usize = 8;
 
+const SBI_EXT_IPI: usize = 0x735049;
+const SBI_IPI_SEND_IPI: usize = 0;
+
+const SBI_EXT_HSM: usize = 0x48534D;
+const SBI_HSM_HART_START: usize = 0;
+
+const SBI_EXT_SRST: usize = 0x53525354;
+const SBI_SRST_RESET_TYPE_SHUTDOWN: usize = 0;
+const SBI_SRST_RESET_REASON_NONE: usize = 0;
+const SBI_SRST_RESET_REASON_SYSFAIL: usize = 1;
+
 #[inline(always)]
 fn sbi_call(which: usize, arg0: usize, arg1: usize, arg2: usize) -> usize {
     let mut ret;
@@ -27,6 +38,24 @@ fn sbi_call(which: usize, arg0: usize, arg1: usize, arg2: usize) -> usize {
     ret
 }
 
+/// sbi call of extension `eid` and function `fid`, return (error, value)
+#[inline(always)]
+fn sbi_call_ext(eid: usize, fid: usize, arg0: usize, arg1: usize, arg2: usize) -> (isize, usize) {
+    let error: isize;
+    let value: usize;
+    unsafe {
+        asm!(
+        "ecall",
+        inlateout("x10") arg0 => error,
+        inlateout("x11") arg1 => value,
+        in("x12") arg2,
+        in("x16") fid,
+        in("x17") eid,
+        );
+    }
+    (error, value)
+}
+
 /// use sbi call to set timer
 pub fn set_timer(timer: usize) {
     sbi_call(SBI_SET_TIMER, timer, 0, 0);
@@ -42,8 +71,26 @@ pub fn console_getchar() -> usize {
     sbi_call(SBI_CONSOLE_GETCHAR, 0, 0, 0)
 }
 
-/// use sbi call to shutdown the kernel
-pub fn shutdown() -> ! {
+/// use sbi call to start hart `hart_id` at `start_addr` with `opaque` in a1, return sbi error code
+pub fn hart_start(hart_id: usize, start_addr: usize, opaque: usize) -> isize {
+    sbi_call_ext(SBI_EXT_HSM, SBI_HSM_HART_START, hart_id, start_addr, opaque).0
+}
+
+/// use sbi call to send a software interrupt to harts in `hart_mask`
+pub fn send_ipi(hart_mask: usize) {
+    sbi_call_ext(SBI_EXT_IPI, SBI_IPI_SEND_IPI, hart_mask, 0, 0);
+}
+
+/// use sbi call to shutdown the kernel, `failure` is reported as exit status of qemu
+///
+/// Fall back to the legacy shutdown if the system reset extension is not supported.
+pub fn shutdown(failure: bool) -> ! {
+    let reason = if failure {
+        SBI_SRST_RESET_REASON_SYSFAIL
+    } else {
+        SBI_SRST_RESET_REASON_NONE
+    };
+    sbi_call_ext(SBI_EXT_SRST, 0, SBI_SRST_RESET_TYPE_SHUTDOWN, reason, 0);
     sbi_call(SBI_SHUTDOWN, 0, 0, 0);
     panic!("It should shutdown!");
 }
@@ -1,21 +1,32 @@
 use crate::println;
 use core::arch::asm;
-use core::ptr;
 
 pub unsafe fn _print_stack_track() {
-    let mut fp: *const usize;
+    let fp: usize;
 
     asm!("mv {}, fp", out(reg) fp);
 
+    print_stack_trace_from(fp);
+}
+
+/// Walk the frame pointer chain starting at `fp`, stop at a frame pointer outside the kernel
+/// image, where all kernel stacks live, so a corrupted chain is not followed.
+pub unsafe fn print_stack_trace_from(mut fp: usize) {
+    extern "C" {
+        fn skernel();
+        fn ekernel();
+    }
+
     println!("=== Start stack trace ===");
 
-    while fp != ptr::null() {
-        let saved_ra = *fp.sub(1);
-        let saved_fp = *fp.sub(2);
+    while fp > skernel as usize && fp <= ekernel as usize && fp % 8 == 0 {
+        let frame = fp as *const usize;
+        let saved_ra = *frame.sub(1);
+        let saved_fp = *frame.sub(2);
 
         println!("0x{:016x}, fp = 0x{:016x}", saved_ra, saved_fp);
 
-        fp = saved_fp as *const usize;
+        fp = saved_fp;
     }
 
     println!("=== End stack trace ===");
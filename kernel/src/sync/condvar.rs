@@ -0,0 +1,34 @@
+use super::wait_queue::WaitQueue;
+
+/// Condition variable used together with a `MutexBlocking`.
+pub struct Condvar {
+    /// tasks blocked in `wait`
+    wait_queue: WaitQueue,
+}
+
+impl Condvar {
+    pub fn new() -> Self {
+        Self {
+            wait_queue: WaitQueue::new(),
+        }
+    }
+
+    /// Wake up the first waiter still alive, nothing happens if there is none.
+    pub fn signal(&self) {
+        self.wait_queue.wake_one();
+    }
+
+    /// Wake up all waiters still alive.
+    pub fn broadcast(&self) {
+        self.wait_queue.wake_all();
+    }
+
+    /// Block until signaled, calling `release` once current task is `Blocked`,
+    /// typically to unlock the mutex protecting the condition, which the caller locks again.
+    ///
+    /// A signal right after `release` is not lost.
+    /// Return false if a signal interrupts the wait.
+    pub fn wait<F: FnOnce()>(&self, release: F) -> bool {
+        self.wait_queue.sleep_on_and_release(release)
+    }
+}
@@ -0,0 +1,60 @@
+use super::spin::SpinLock;
+use super::wait_queue::WaitQueue;
+use crate::syscall::{SysError, SysResult};
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use lazy_static::lazy_static;
+
+lazy_static! {
+    /// tasks waiting on each futex word, keyed by its physical address
+    static ref FUTEX_QUEUES: SpinLock<BTreeMap<usize, Arc<WaitQueue>>> =
+        SpinLock::new(BTreeMap::new());
+}
+
+/// Block current task on the futex word at physical address `pa` if it still holds `expected`,
+/// `EAGAIN` at once otherwise, `EINTR` if a signal interrupts the wait.
+///
+/// The word is read with the queues locked, so a wake after changing it is never lost.
+pub fn futex_wait(pa: usize, expected: u32) -> SysResult {
+    let mut queues = FUTEX_QUEUES.lock();
+    let value = unsafe { (pa as *const u32).read_volatile() };
+    if value != expected {
+        return Err(SysError::EAGAIN);
+    }
+    let queue = queues
+        .entry(pa)
+        .or_insert_with(|| Arc::new(WaitQueue::new()))
+        .clone();
+    if !queue.sleep_on_and_release(|| drop(queues)) {
+        // like `futex_wake`, drop the queue once nobody waits on it, unless it has been replaced
+        let mut queues = FUTEX_QUEUES.lock();
+        if queue.is_empty() && queues.get(&pa).map_or(false, |q| Arc::ptr_eq(q, &queue)) {
+            queues.remove(&pa);
+        }
+        return Err(SysError::EINTR);
+    }
+    Ok(0)
+}
+
+/// Wake up at most `count` tasks waiting on the futex word at physical address `pa`,
+/// return how many were woken.
+///
+/// `count` of `i32::MAX` or more wakes up all of them, libc passes `INT_MAX` for that.
+pub fn futex_wake(pa: usize, count: usize) -> usize {
+    let mut queues = FUTEX_QUEUES.lock();
+    let queue = match queues.get(&pa) {
+        Some(queue) => queue.clone(),
+        None => return 0,
+    };
+    let woken = if count >= i32::MAX as usize {
+        queue.wake_all()
+    } else {
+        (0..count)
+            .take_while(|_| queue.wake_one().is_some())
+            .count()
+    };
+    if queue.is_empty() {
+        queues.remove(&pa);
+    }
+    woken
+}
@@ -0,0 +1,63 @@
+use crate::config::MAX_HARTS;
+use crate::riscv::hart_id;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use riscv::register::sstatus;
+
+/// Interrupt disable state of a hart, only touched by the hart itself.
+struct IntrState {
+    /// depth of nested `push_off`
+    noff: AtomicUsize,
+    /// whether interrupts were enabled before the outermost `push_off`
+    intena: AtomicBool,
+}
+
+#[allow(clippy::declare_interior_mutable_const)]
+const INTR_STATE_INIT: IntrState = IntrState {
+    noff: AtomicUsize::new(0),
+    intena: AtomicBool::new(false),
+};
+
+static INTR_STATES: [IntrState; MAX_HARTS] = [INTR_STATE_INIT; MAX_HARTS];
+
+/// Disable interrupts of current hart, it takes as many `pop_off` to enable them again.
+pub fn push_off() {
+    let enabled = sstatus::read().sie();
+    unsafe {
+        sstatus::clear_sie();
+    }
+    let state = &INTR_STATES[hart_id()];
+    if state.noff.load(Ordering::Relaxed) == 0 {
+        state.intena.store(enabled, Ordering::Relaxed);
+    }
+    state.noff.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Undo one `push_off`, interrupts are enabled again if they were before the outermost one.
+pub fn pop_off() {
+    assert!(!sstatus::read().sie(), "pop_off with interrupts enabled");
+    let state = &INTR_STATES[hart_id()];
+    let noff = state.noff.load(Ordering::Relaxed);
+    assert!(noff > 0, "pop_off without push_off");
+    state.noff.store(noff - 1, Ordering::Relaxed);
+    if noff == 1 && state.intena.load(Ordering::Relaxed) {
+        unsafe {
+            sstatus::set_sie();
+        }
+    }
+}
+
+/// Disables interrupts of current hart while alive.
+pub struct IntrGuard;
+
+impl IntrGuard {
+    pub fn new() -> Self {
+        push_off();
+        Self
+    }
+}
+
+impl Drop for IntrGuard {
+    fn drop(&mut self) {
+        pop_off();
+    }
+}
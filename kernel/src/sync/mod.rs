@@ -1,3 +1,15 @@
-mod up;
+mod condvar;
+mod futex;
+mod intr;
+mod mutex;
+mod semaphore;
+mod spin;
+mod wait_queue;
 
-pub use up::UPSafeCell;
+pub use condvar::Condvar;
+pub use futex::{futex_wait, futex_wake};
+pub use intr::{pop_off, push_off, IntrGuard};
+pub use mutex::{Mutex, MutexBlocking, MutexGuard};
+pub use semaphore::Semaphore;
+pub use spin::{SpinLock, SpinLockGuard};
+pub use wait_queue::WaitQueue;
@@ -0,0 +1,134 @@
+use super::spin::SpinLock;
+use super::wait_queue::WaitQueue;
+use crate::syscall::SysError;
+use crate::task::current_task_id;
+use core::cell::UnsafeCell;
+use core::ops::{Deref, DerefMut};
+
+/// Sleeping lock without data, tasks waiting for it are `Blocked`.
+///
+/// Only usable by tasks, never by the idle loop or an interrupt handler.
+/// The lock is handed over to the first waiter on unlock, so waiters get it in order.
+/// Backs both `Mutex` and the mutexes user programs create by `sys_mutex_create`.
+pub struct MutexBlocking {
+    /// task holding the lock, also for debugging
+    owner: SpinLock<Option<usize>>,
+    /// tasks blocked on the lock
+    wait_queue: WaitQueue,
+}
+
+impl MutexBlocking {
+    pub fn new() -> Self {
+        Self {
+            owner: SpinLock::new(None),
+            wait_queue: WaitQueue::new(),
+        }
+    }
+
+    /// Panic if current task already holds the lock, which would block forever.
+    pub fn lock(&self) {
+        self.lock_checked(false, |_| true).unwrap();
+    }
+
+    /// Like `lock`, but first call `check` with whether the lock is free, and give up
+    /// with `EDEADLK` if it refuses. `check` runs under the state of the lock,
+    /// so what it decides on cannot change before the lock is taken or waited for.
+    ///
+    /// If `interruptible`, waiting for the lock fails with `EINTR` when a signal arrives.
+    pub fn lock_checked<F: FnOnce(bool) -> bool>(
+        &self,
+        interruptible: bool,
+        check: F,
+    ) -> Result<(), SysError> {
+        let current = current_task_id();
+        let mut owner = self.owner.lock();
+        let holder = *owner;
+        if !check(holder.is_none()) {
+            return Err(SysError::EDEADLK);
+        }
+        match holder {
+            None => *owner = Some(current),
+            Some(task) => {
+                assert_ne!(task, current, "Mutex locked twice by task {}", current);
+                if !interruptible {
+                    self.wait_queue.sleep_on_uninterruptible(|| drop(owner));
+                } else if !self.wait_queue.sleep_on_and_release(|| drop(owner)) {
+                    return Err(SysError::EINTR);
+                }
+                // the unlocking task has made us the owner
+            }
+        }
+        Ok(())
+    }
+
+    /// Hand the lock over to the first waiter still alive, or free it if there is none.
+    pub fn unlock(&self) {
+        self.unlock_with(|_| {});
+    }
+
+    /// Like `unlock`, then call `handover` with the task the lock is handed over to,
+    /// still under the state of the lock.
+    pub fn unlock_with<F: FnOnce(Option<usize>)>(&self, handover: F) {
+        let mut owner = self.owner.lock();
+        assert!(owner.is_some(), "Unlock a free mutex");
+        *owner = self.wait_queue.wake_one();
+        handover(*owner);
+    }
+
+    /// Task holding the lock, if any.
+    pub fn owner(&self) -> Option<usize> {
+        *self.owner.lock()
+    }
+}
+
+/// Sleeping lock for long critical sections, see `MutexBlocking`.
+pub struct Mutex<T> {
+    raw: MutexBlocking,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: Send> Sync for Mutex<T> {}
+unsafe impl<T: Send> Send for Mutex<T> {}
+
+pub struct MutexGuard<'a, T> {
+    mutex: &'a Mutex<T>,
+}
+
+impl<T> Mutex<T> {
+    pub fn new(data: T) -> Self {
+        Self {
+            raw: MutexBlocking::new(),
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    pub fn lock(&self) -> MutexGuard<'_, T> {
+        self.raw.lock();
+        MutexGuard { mutex: self }
+    }
+
+    /// Task holding the mutex, if any.
+    pub fn owner(&self) -> Option<usize> {
+        self.raw.owner()
+    }
+}
+
+impl<T> Deref for MutexGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.mutex.data.get() }
+    }
+}
+
+impl<T> DerefMut for MutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.mutex.data.get() }
+    }
+}
+
+impl<T> Drop for MutexGuard<'_, T> {
+    fn drop(&mut self) {
+        self.mutex.raw.unlock();
+    }
+}
@@ -0,0 +1,55 @@
+use super::spin::SpinLock;
+use super::wait_queue::WaitQueue;
+use crate::syscall::SysError;
+
+/// Counting semaphore, tasks waiting for it are `Blocked`.
+pub struct Semaphore {
+    /// available resources, or the number of waiters if negative
+    count: SpinLock<isize>,
+    /// tasks blocked in `down`
+    wait_queue: WaitQueue,
+}
+
+impl Semaphore {
+    pub fn new(count: usize) -> Self {
+        Self {
+            count: SpinLock::new(count as isize),
+            wait_queue: WaitQueue::new(),
+        }
+    }
+
+    /// Release a resource and wake up the first waiter still alive, then call `handover` with
+    /// the task woken, which takes the resource, still under the count.
+    pub fn up<F: FnOnce(Option<usize>)>(&self, handover: F) {
+        let mut count = self.count.lock();
+        *count += 1;
+        let mut woken = None;
+        while *count <= 0 {
+            woken = self.wait_queue.wake_one();
+            if woken.is_some() {
+                break;
+            }
+            // give back the resource taken by a waiter which has been killed or interrupted
+            *count += 1;
+        }
+        handover(woken);
+    }
+
+    /// Take a resource, block until one is released if there is none.
+    ///
+    /// `check` is first called under the count with whether a resource is free,
+    /// and nothing is taken with `EDEADLK` if it returns false.
+    /// `EINTR` if a signal interrupts the wait, the resource counted for it is given back
+    /// by a later `up`, which finds no waiter for it.
+    pub fn down<F: FnOnce(bool) -> bool>(&self, check: F) -> Result<(), SysError> {
+        let mut count = self.count.lock();
+        if !check(*count > 0) {
+            return Err(SysError::EDEADLK);
+        }
+        *count -= 1;
+        if *count < 0 && !self.wait_queue.sleep_on_and_release(|| drop(count)) {
+            return Err(SysError::EINTR);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,92 @@
+use super::intr::IntrGuard;
+use crate::riscv::hart_id;
+use core::cell::UnsafeCell;
+use core::hint::spin_loop;
+use core::ops::{Deref, DerefMut};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// `SpinLock::owner` when nobody holds the lock
+const NO_OWNER: usize = usize::MAX;
+
+/// Ticket lock spinning until it is free, safe to share between harts.
+///
+/// Harts get the lock in the order they asked for it. Interrupts of the holding hart are
+/// disabled until the guard is dropped, so an interrupt handler never spins on a lock
+/// held by the code it interrupted.
+pub struct SpinLock<T> {
+    next_ticket: AtomicUsize,
+    now_serving: AtomicUsize,
+    /// hart holding the lock, for debugging
+    owner: AtomicUsize,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: Send> Sync for SpinLock<T> {}
+unsafe impl<T: Send> Send for SpinLock<T> {}
+
+pub struct SpinLockGuard<'a, T> {
+    lock: &'a SpinLock<T>,
+    _intr: IntrGuard,
+}
+
+impl<T> SpinLock<T> {
+    pub const fn new(data: T) -> Self {
+        Self {
+            next_ticket: AtomicUsize::new(0),
+            now_serving: AtomicUsize::new(0),
+            owner: AtomicUsize::new(NO_OWNER),
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    /// Panic if current hart already holds the lock, which would spin forever.
+    pub fn lock(&self) -> SpinLockGuard<'_, T> {
+        let intr = IntrGuard::new();
+        let hart = hart_id();
+        assert_ne!(
+            self.owner.load(Ordering::Relaxed),
+            hart,
+            "SpinLock locked twice by hart {}",
+            hart
+        );
+        let ticket = self.next_ticket.fetch_add(1, Ordering::Relaxed);
+        while self.now_serving.load(Ordering::Acquire) != ticket {
+            spin_loop();
+        }
+        self.owner.store(hart, Ordering::Relaxed);
+        SpinLockGuard {
+            lock: self,
+            _intr: intr,
+        }
+    }
+
+    /// Hart holding the lock, if any.
+    pub fn owner(&self) -> Option<usize> {
+        match self.owner.load(Ordering::Relaxed) {
+            NO_OWNER => None,
+            hart => Some(hart),
+        }
+    }
+}
+
+impl<T> Deref for SpinLockGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T> DerefMut for SpinLockGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<T> Drop for SpinLockGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.owner.store(NO_OWNER, Ordering::Relaxed);
+        self.lock.now_serving.fetch_add(1, Ordering::Release);
+        // `_intr` is dropped after the lock is released
+    }
+}
@@ -0,0 +1,76 @@
+use super::spin::SpinLock;
+use crate::task::{block_current_and_run_next_with, current_task_id, wakeup_task};
+use alloc::collections::VecDeque;
+
+/// Tasks `Blocked` until some event, woken in the order they came.
+///
+/// A task is `Blocked` before it is visible in the queue to wakers, and the queue lock keeps
+/// interrupts off, so neither another hart nor an interrupt handler can lose a wakeup.
+pub struct WaitQueue {
+    queue: SpinLock<VecDeque<usize>>,
+}
+
+impl WaitQueue {
+    pub fn new() -> Self {
+        Self {
+            queue: SpinLock::new(VecDeque::new()),
+        }
+    }
+
+    /// Block current task until woken by `wake_one` or `wake_all`,
+    /// return false if a signal interrupts it first.
+    pub fn sleep_on(&self) -> bool {
+        self.sleep_on_and_release(|| {})
+    }
+
+    /// Block current task like `sleep_on`, calling `release` once it is in the queue,
+    /// typically to release the lock under which the event is checked and signaled.
+    pub fn sleep_on_and_release<F: FnOnce()>(&self, release: F) -> bool {
+        self.wait(true, release)
+    }
+
+    /// Like `sleep_on_and_release`, but signals do not wake the task up,
+    /// for waits which must not fail such as taking back a lock.
+    pub fn sleep_on_uninterruptible<F: FnOnce()>(&self, release: F) {
+        self.wait(false, release);
+    }
+
+    /// An interrupted task leaves the queue itself, no waker can pick it any more:
+    /// `wakeup_task` fails on it as it is not `Blocked` since the interruption.
+    fn wait<F: FnOnce()>(&self, interruptible: bool, release: F) -> bool {
+        let current = current_task_id();
+        let mut queue = self.queue.lock();
+        queue.push_back(current);
+        let woken = block_current_and_run_next_with(interruptible, || {
+            drop(queue);
+            release();
+        });
+        if !woken {
+            self.queue.lock().retain(|&task| task != current);
+        }
+        woken
+    }
+
+    /// Wake up the first task still waiting, tasks killed meanwhile are skipped.
+    ///
+    /// Return the id of the task woken, `None` if there is none.
+    pub fn wake_one(&self) -> Option<usize> {
+        let mut queue = self.queue.lock();
+        while let Some(task) = queue.pop_front() {
+            if wakeup_task(task) {
+                return Some(task);
+            }
+        }
+        None
+    }
+
+    /// Wake up all tasks waiting, return how many were woken.
+    pub fn wake_all(&self) -> usize {
+        let mut queue = self.queue.lock();
+        queue.drain(..).filter(|&task| wakeup_task(task)).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.lock().is_empty()
+    }
+}
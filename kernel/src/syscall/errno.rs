@@ -0,0 +1,47 @@
+/// Error of a syscall, returned to user mode as `-errno` in a0 like Linux.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SysError {
+    /// operation not permitted
+    EPERM = 1,
+    /// no such file or directory
+    ENOENT = 2,
+    /// no such process
+    ESRCH = 3,
+    /// interrupted by a signal
+    EINTR = 4,
+    /// bad file descriptor
+    EBADF = 9,
+    /// no child (thread) to wait for
+    ECHILD = 10,
+    /// try again
+    EAGAIN = 11,
+    /// out of memory
+    ENOMEM = 12,
+    /// bad address
+    EFAULT = 14,
+    /// resource busy
+    EBUSY = 16,
+    /// invalid argument
+    EINVAL = 22,
+    /// resource deadlock would occur
+    EDEADLK = 35,
+    /// function not implemented
+    ENOSYS = 38,
+}
+
+/// Result of a syscall handler, converted to the value in a0 by `SysError::encode`.
+pub type SysResult = Result<isize, SysError>;
+
+impl SysError {
+    pub fn errno(self) -> isize {
+        self as isize
+    }
+
+    /// Value returned to user mode for `result`.
+    pub fn encode(result: SysResult) -> isize {
+        match result {
+            Ok(ret) => ret,
+            Err(err) => -err.errno(),
+        }
+    }
+}
@@ -1,17 +1,84 @@
-use crate::print;
+use super::{SysError, SysResult};
+use crate::fs::File;
+use crate::mm::{read_user, translated_byte_buffer};
+use crate::task::with_current_process;
+use alloc::sync::Arc;
 
-const FD_STDOUT: usize = 1;
+/// Open file `fd` of current process, `EBADF` if it is not open.
+fn get_file(fd: usize) -> Result<Arc<dyn File>, SysError> {
+    with_current_process(|process| process.get_file(fd))
+        .flatten()
+        .ok_or(SysError::EBADF)
+}
 
-pub fn sys_write(fd: usize, buf: *const u8, len: usize) -> isize {
-    match fd {
-        FD_STDOUT => {
-            let slice = unsafe { core::slice::from_raw_parts(buf, len) };
-            let str = core::str::from_utf8(slice).unwrap();
-            print!("{}", str);
-            len as isize
-        }
-        _ => {
-            panic!("Unsupported fd in sys_write!");
+/// read at most `len` bytes from `fd` into `buf`, return how many bytes were read,
+/// `EBADF` if `fd` is not open for reading, `EFAULT` if `buf` is not writable
+pub fn sys_read(fd: usize, buf: usize, len: usize) -> SysResult {
+    let file = get_file(fd)?;
+    if !file.readable() {
+        return Err(SysError::EBADF);
+    }
+    let buffers = translated_byte_buffer(buf, len, true).ok_or(SysError::EFAULT)?;
+    // the process lock is released, reading may block
+    let mut total = 0;
+    for buffer in buffers {
+        let read = file.read(buffer)?;
+        total += read;
+        // stop at a short read rather than block for the next page
+        if (read as usize) < buffer.len() {
+            break;
         }
     }
+    Ok(total)
+}
+
+/// write `len` bytes at `buf` to `fd`, return how many bytes were written,
+/// `EBADF` if `fd` is not open for writing, `EFAULT` if `buf` is not readable
+pub fn sys_write(fd: usize, buf: usize, len: usize) -> SysResult {
+    let file = get_file(fd)?;
+    if !file.writable() {
+        return Err(SysError::EBADF);
+    }
+    let buffers = translated_byte_buffer(buf, len, false).ok_or(SysError::EFAULT)?;
+    let mut total = 0;
+    for buffer in buffers {
+        total += file.write(buffer)?;
+    }
+    Ok(total)
+}
+
+/// `struct iovec` of Linux, the base and length of a buffer
+type IoVec = [usize; 2];
+
+/// write `iovcnt` buffers described by `iov` to `fd` in order, return the total bytes written,
+/// `EFAULT` if `iov` or a buffer is not readable
+pub fn sys_writev(fd: usize, iov: usize, iovcnt: usize) -> SysResult {
+    let mut total = 0;
+    for i in 0..iovcnt {
+        let [base, len] = core::mem::size_of::<IoVec>()
+            .checked_mul(i)
+            .and_then(|offset| read_user::<IoVec>(iov.checked_add(offset)?))
+            .ok_or(SysError::EFAULT)?;
+        total += sys_write(fd, base, len)?;
+    }
+    Ok(total)
+}
+
+/// open file `fd` again at the lowest free fd and return the new fd, `EBADF` if it is not open
+pub fn sys_dup(fd: usize) -> SysResult {
+    let file = get_file(fd)?;
+    with_current_process(|process| process.add_file(file) as isize).ok_or(SysError::EBADF)
+}
+
+/// open a file, always `ENOENT` as there is no file system yet
+pub fn sys_openat(_dirfd: isize, _path: usize, _flags: usize, _mode: usize) -> SysResult {
+    Err(SysError::ENOENT)
+}
+
+/// close `fd` of current process, `EBADF` if it is not open
+pub fn sys_close(fd: usize) -> SysResult {
+    with_current_process(|process| process.close_file(fd))
+        .flatten()
+        .ok_or(SysError::EBADF)?;
+    Ok(0)
 }
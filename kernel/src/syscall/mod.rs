@@ -1,21 +1,124 @@
+const SYSCALL_DUP: usize = 23;
+const SYSCALL_OPENAT: usize = 56;
+const SYSCALL_CLOSE: usize = 57;
+const SYSCALL_READ: usize = 63;
 const SYSCALL_WRITE: usize = 64;
+const SYSCALL_WRITEV: usize = 66;
 const SYSCALL_EXIT: usize = 93;
+const SYSCALL_EXIT_GROUP: usize = 94;
+const SYSCALL_SET_TID_ADDRESS: usize = 96;
+const SYSCALL_FUTEX: usize = 98;
+const SYSCALL_NANOSLEEP: usize = 101;
+const SYSCALL_CLOCK_GETTIME: usize = 113;
 const SYSCALL_YIELD: usize = 124;
-const SYSCALL_GET_TIME: usize = 169;
+const SYSCALL_KILL: usize = 129;
+const SYSCALL_GETTID: usize = 178;
+const SYSCALL_BRK: usize = 214;
+const SYSCALL_MMAP: usize = 222;
+const SYSCALL_TASK_INFO: usize = 410;
+const SYSCALL_ENABLE_DEADLOCK_DETECT: usize = 469;
+const SYSCALL_THREAD_CREATE: usize = 1000;
+const SYSCALL_WAITTID: usize = 1002;
+const SYSCALL_MUTEX_CREATE: usize = 1010;
+const SYSCALL_MUTEX_LOCK: usize = 1011;
+const SYSCALL_MUTEX_UNLOCK: usize = 1012;
+const SYSCALL_SEMAPHORE_CREATE: usize = 1020;
+const SYSCALL_SEMAPHORE_UP: usize = 1021;
+const SYSCALL_SEMAPHORE_DOWN: usize = 1022;
+const SYSCALL_CONDVAR_CREATE: usize = 1030;
+const SYSCALL_CONDVAR_SIGNAL: usize = 1031;
+const SYSCALL_CONDVAR_WAIT: usize = 1032;
+const SYSCALL_CONDVAR_BROADCAST: usize = 1033;
+const SYSCALL_TRACE: usize = 1040;
+const SYSCALL_SIGACTION: usize = 1050;
+const SYSCALL_SIGPROCMASK: usize = 1051;
+const SYSCALL_SIGRETURN: usize = 1052;
+const SYSCALL_SET_PRIORITY: usize = 1060;
+const SYSCALL_SETRLIMIT: usize = 1061;
+const SYSCALL_SCHED_SETATTR: usize = 1062;
+const SYSCALL_GET_TIME: usize = 1063;
 
+mod errno;
 mod fs;
 mod process;
+mod signal;
+mod sync;
+mod thread;
+mod trace;
 
+use crate::task::{count_current_syscall, current_task_id, current_trace_mask};
+use crate::timer::get_time;
+use crate::trap::TrapContext;
 use fs::*;
 use process::*;
+use signal::*;
+use sync::*;
+use thread::*;
+use trace::*;
 
-/// handle syscall exception with `syscall_id` and other arguments
-pub fn syscall(syscall_id: usize, args: [usize; 3]) -> isize {
+pub use errno::{SysError, SysResult};
+pub use trace::build_trace_mask;
+
+/// handle syscall exception with `syscall_id` and other arguments,
+/// return the value for a0, `-errno` on error
+///
+/// `cx` is the user context of current task, which `sigreturn` replaces.
+pub fn syscall(syscall_id: usize, args: [usize; 6], cx: &mut TrapContext) -> isize {
+    count_current_syscall(syscall_id);
+    if !is_traced(syscall_id, current_trace_mask()) {
+        return SysError::encode(dispatch(syscall_id, args, cx));
+    }
+    let task_id = current_task_id();
+    if syscall_id == SYSCALL_EXIT || syscall_id == SYSCALL_EXIT_GROUP {
+        log_syscall(task_id, syscall_id, args, None, 0);
+    }
+    let start = get_time();
+    let result = dispatch(syscall_id, args, cx);
+    log_syscall(task_id, syscall_id, args, Some(result), get_time() - start);
+    SysError::encode(result)
+}
+
+fn dispatch(syscall_id: usize, args: [usize; 6], cx: &mut TrapContext) -> SysResult {
     match syscall_id {
-        SYSCALL_WRITE => sys_write(args[0], args[1] as *const u8, args[2]),
+        SYSCALL_DUP => sys_dup(args[0]),
+        SYSCALL_OPENAT => sys_openat(args[0] as isize, args[1], args[2], args[3]),
+        SYSCALL_CLOSE => sys_close(args[0]),
+        SYSCALL_READ => sys_read(args[0], args[1], args[2]),
+        SYSCALL_WRITE => sys_write(args[0], args[1], args[2]),
+        SYSCALL_WRITEV => sys_writev(args[0], args[1], args[2]),
         SYSCALL_EXIT => sys_exit(args[0] as i32),
+        SYSCALL_EXIT_GROUP => sys_exit_group(args[0] as i32),
+        SYSCALL_SET_TID_ADDRESS => sys_set_tid_address(args[0]),
+        SYSCALL_FUTEX => sys_futex(args[0], args[1], args[2]),
+        SYSCALL_NANOSLEEP => sys_nanosleep(args[0]),
+        SYSCALL_CLOCK_GETTIME => sys_clock_gettime(args[0], args[1]),
         SYSCALL_YIELD => sys_yield(),
+        SYSCALL_KILL => sys_kill(args[0], args[1]),
+        SYSCALL_SIGACTION => sys_sigaction(args[0], args[1], args[2]),
+        SYSCALL_SIGPROCMASK => sys_sigprocmask(args[0] as u32),
+        SYSCALL_SIGRETURN => sys_sigreturn(cx),
+        SYSCALL_SET_PRIORITY => sys_set_priority(args[0] as isize),
+        SYSCALL_SETRLIMIT => sys_setrlimit(args[0], args[1]),
         SYSCALL_GET_TIME => sys_get_time(),
-        _ => panic!("Unsupported syscall_id: {}", syscall_id),
+        SYSCALL_BRK => sys_brk(args[0]),
+        SYSCALL_MMAP => sys_mmap(args[0], args[1], args[2], args[3], args[4], args[5]),
+        SYSCALL_SCHED_SETATTR => sys_sched_setattr(args[0], args[1], args[2]),
+        SYSCALL_TASK_INFO => sys_task_info(args[0]),
+        SYSCALL_ENABLE_DEADLOCK_DETECT => sys_enable_deadlock_detect(args[0]),
+        SYSCALL_THREAD_CREATE => sys_thread_create(args[0], args[1]),
+        SYSCALL_GETTID => sys_gettid(),
+        SYSCALL_WAITTID => sys_waittid(args[0], args[1]),
+        SYSCALL_MUTEX_CREATE => sys_mutex_create(),
+        SYSCALL_MUTEX_LOCK => sys_mutex_lock(args[0]),
+        SYSCALL_MUTEX_UNLOCK => sys_mutex_unlock(args[0]),
+        SYSCALL_SEMAPHORE_CREATE => sys_semaphore_create(args[0]),
+        SYSCALL_SEMAPHORE_UP => sys_semaphore_up(args[0]),
+        SYSCALL_SEMAPHORE_DOWN => sys_semaphore_down(args[0]),
+        SYSCALL_CONDVAR_CREATE => sys_condvar_create(),
+        SYSCALL_CONDVAR_SIGNAL => sys_condvar_signal(args[0]),
+        SYSCALL_CONDVAR_WAIT => sys_condvar_wait(args[0], args[1]),
+        SYSCALL_CONDVAR_BROADCAST => sys_condvar_broadcast(args[0]),
+        SYSCALL_TRACE => sys_trace(args[0], args[1]),
+        _ => Err(SysError::ENOSYS),
     }
 }
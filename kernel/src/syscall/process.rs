@@ -1,21 +1,159 @@
+use super::{SysError, SysResult};
+use crate::mm::{read_user, write_user};
+use crate::task::{
+    current_task_info, current_usage_summary, exit_current_and_run_next,
+    exit_current_group_and_run_next, set_current_clear_child_tid, set_current_deadline,
+    set_current_nice, set_current_rlimit, sleep_current_until, with_current_process,
+    yield_current_and_run_next, TaskInfo,
+};
+use crate::timer::{get_time, get_time_ms, time_to_us, timespec_to_time};
+use alloc::alloc::{alloc_zeroed, Layout};
+use alloc::boxed::Box;
+
 use crate::println;
-use crate::task::{exit_current_and_run_next, suspend_current_and_run_next};
-use crate::timer::get_time_ms;
+
+const NSEC_PER_SEC: usize = 1_000_000_000;
+
+#[derive(Copy, Clone)]
+#[repr(C)]
+pub struct TimeSpec {
+    pub sec: usize,
+    pub nsec: usize,
+}
 
 /// task exits and submit an exit code
 pub fn sys_exit(exit_code: i32) -> ! {
-    println!("[kernel] Application exited with code {}", exit_code);
-    exit_current_and_run_next();
+    report_exit(exit_code);
+    exit_current_and_run_next(exit_code);
     panic!("Unreachable in sys_exit!");
 }
 
-/// current task gives up resources for other tasks
-pub fn sys_yield() -> isize {
-    suspend_current_and_run_next();
-    0
+/// all threads of current process exit with `exit_code`
+pub fn sys_exit_group(exit_code: i32) -> ! {
+    report_exit(exit_code);
+    exit_current_group_and_run_next(exit_code);
+    panic!("Unreachable in sys_exit_group!");
+}
+
+fn report_exit(exit_code: i32) {
+    println!(
+        "[kernel] Application exited with code {}, {}",
+        exit_code,
+        current_usage_summary()
+    );
+}
+
+/// block current task for the duration in `req`, `EFAULT` if `req` is not readable,
+/// `EINVAL` if `nsec` is not below one second or the duration overflows,
+/// `EINTR` if a signal interrupts the sleep
+pub fn sys_nanosleep(req: usize) -> SysResult {
+    let req = read_user::<TimeSpec>(req).ok_or(SysError::EFAULT)?;
+    if req.nsec >= NSEC_PER_SEC {
+        return Err(SysError::EINVAL);
+    }
+    let expire = timespec_to_time(req.sec, req.nsec)
+        .and_then(|duration| get_time().checked_add(duration))
+        .ok_or(SysError::EINVAL)?;
+    if !sleep_current_until(expire) {
+        return Err(SysError::EINTR);
+    }
+    Ok(0)
+}
+
+/// current task gives up resources for other tasks,
+/// a real-time task also ends its job and sleeps until the next period
+pub fn sys_yield() -> SysResult {
+    yield_current_and_run_next();
+    Ok(0)
+}
+
+/// set nice value of current task, return the new value or `EINVAL` if out of [-20, 19]
+pub fn sys_set_priority(nice: isize) -> SysResult {
+    set_current_nice(nice)
+}
+
+/// set a resource limit of current task: `RLIMIT_CPU` and `RLIMIT_WALL` in milliseconds,
+/// `RLIMIT_AS` in pages for the whole process, `RLIM_INFINITY` for no limit;
+/// `EINVAL` if `resource` is unknown
+pub fn sys_setrlimit(resource: usize, limit: usize) -> SysResult {
+    set_current_rlimit(resource, limit)
+}
+
+/// make current task periodic real-time, all arguments are in microseconds,
+/// `EINVAL` if `runtime <= deadline <= period` is broken or they overflow,
+/// `EBUSY` if the total density `runtime / deadline` of real-time tasks would exceed 1
+pub fn sys_sched_setattr(runtime: usize, deadline: usize, period: usize) -> SysResult {
+    set_current_deadline(runtime, deadline, period)
+}
+
+/// set `clear_child_tid` of current thread, cleared and futex-woken when it exits,
+/// return its tid
+pub fn sys_set_tid_address(tidptr: usize) -> SysResult {
+    Ok(set_current_clear_child_tid(tidptr) as isize)
+}
+
+const CLOCK_REALTIME: usize = 0;
+const CLOCK_MONOTONIC: usize = 1;
+
+/// get the time of clock `clock_id` into `tp`, both clocks count from boot as there is no rtc,
+/// `EINVAL` for other clocks, `EFAULT` if `tp` is not writable
+pub fn sys_clock_gettime(clock_id: usize, tp: usize) -> SysResult {
+    if clock_id != CLOCK_REALTIME && clock_id != CLOCK_MONOTONIC {
+        return Err(SysError::EINVAL);
+    }
+    let us = time_to_us(get_time());
+    let time = TimeSpec {
+        sec: us / 1_000_000,
+        nsec: us % 1_000_000 * 1000,
+    };
+    write_user(tp, &time).ok_or(SysError::EFAULT)?;
+    Ok(0)
+}
+
+/// move the program break of current process to `addr` and return the new break,
+/// the break stays and is returned unchanged if `addr` is 0 or out of the free memory
+pub fn sys_brk(addr: usize) -> SysResult {
+    with_current_process(|process| process.set_brk(addr) as isize).ok_or(SysError::EINVAL)
+}
+
+const MAP_FIXED: usize = 0x10;
+const MAP_ANONYMOUS: usize = 0x20;
+
+/// map `len` bytes of zeroed memory and return its address, only anonymous mappings at an
+/// address chosen by the kernel are supported, `prot` is ignored without paging;
+/// `EINVAL` for other mappings, `ENOMEM` if the free memory of the process is used up
+/// or `RLIMIT_AS` would be exceeded
+pub fn sys_mmap(
+    _addr: usize,
+    len: usize,
+    _prot: usize,
+    flags: usize,
+    _fd: usize,
+    _offset: usize,
+) -> SysResult {
+    if len == 0 || flags & MAP_ANONYMOUS == 0 || flags & MAP_FIXED != 0 {
+        return Err(SysError::EINVAL);
+    }
+    with_current_process(|process| process.alloc_anonymous(len))
+        .ok_or(SysError::EINVAL)?
+        .map(|addr| addr as isize)
+        .ok_or(SysError::ENOMEM)
 }
 
 /// get time in milliseconds
-pub fn sys_get_time() -> isize {
-    get_time_ms() as isize
+pub fn sys_get_time() -> SysResult {
+    Ok(get_time_ms() as isize)
+}
+
+/// copy statistics of current task into `ti`, `EFAULT` if `ti` is not writable
+pub fn sys_task_info(ti: usize) -> SysResult {
+    // too large for a kernel stack, and all zero bits is a valid `TaskInfo`
+    let ptr = unsafe { alloc_zeroed(Layout::new::<TaskInfo>()) } as *mut TaskInfo;
+    if ptr.is_null() {
+        return Err(SysError::ENOMEM);
+    }
+    let mut info = unsafe { Box::from_raw(ptr) };
+    current_task_info(&mut info);
+    write_user(ti, &*info).ok_or(SysError::EFAULT)?;
+    Ok(0)
 }
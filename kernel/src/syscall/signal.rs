@@ -0,0 +1,43 @@
+use super::{SysError, SysResult};
+use crate::mm::{read_user, translated_byte_buffer, write_user};
+use crate::task::{
+    current_sigreturn, kill, set_current_sigaction, set_current_sigmask, SignalAction,
+};
+use crate::trap::TrapContext;
+
+/// send signal `signum` to process `pid`, `ESRCH` if there is no such process,
+/// `EINVAL` if `signum` is invalid
+pub fn sys_kill(pid: usize, signum: usize) -> SysResult {
+    kill(pid, signum)
+}
+
+/// install `action` for `signum` if not null, and save the old one into `old_action` if not null,
+/// `EINVAL` if `signum` is invalid or can not be caught,
+/// `EFAULT` if `action` is not readable or `old_action` is not writable
+pub fn sys_sigaction(signum: usize, action: usize, old_action: usize) -> SysResult {
+    let action = match action {
+        0 => None,
+        action => Some(read_user::<SignalAction>(action).ok_or(SysError::EFAULT)?),
+    };
+    // checked before the action is changed
+    let size = core::mem::size_of::<SignalAction>();
+    if old_action != 0 && translated_byte_buffer(old_action, size, true).is_none() {
+        return Err(SysError::EFAULT);
+    }
+    let old = set_current_sigaction(signum, action).ok_or(SysError::EINVAL)?;
+    if old_action != 0 {
+        write_user(old_action, &old).ok_or(SysError::EFAULT)?;
+    }
+    Ok(0)
+}
+
+/// set blocked signals of current thread, return the old mask
+pub fn sys_sigprocmask(mask: u32) -> SysResult {
+    Ok(set_current_sigmask(mask) as isize)
+}
+
+/// return from a signal handler to the interrupted context,
+/// `EINVAL` if not called from a signal handler
+pub fn sys_sigreturn(cx: &mut TrapContext) -> SysResult {
+    current_sigreturn(cx)
+}
@@ -0,0 +1,215 @@
+use super::{SysError, SysResult};
+use crate::mm::translate_user_addr;
+use crate::sync::{futex_wait, futex_wake, Condvar, MutexBlocking, Semaphore};
+use crate::task::{current_task_id, current_tid, with_current_process};
+
+const FUTEX_WAIT: usize = 0;
+const FUTEX_WAKE: usize = 1;
+
+/// create a mutex in current process, return its id or `EINVAL` if the caller is not a user task
+pub fn sys_mutex_create() -> SysResult {
+    with_current_process(|process| process.add_mutex(MutexBlocking::new()) as isize)
+        .ok_or(SysError::EINVAL)
+}
+
+/// lock mutex `mutex_id`, block until it is free,
+/// `EINVAL` if there is no such mutex, `EDEADLK` if the caller holds it already
+/// or deadlock detection is on and waiting for it would be unsafe,
+/// `EINTR` if a signal interrupts the wait
+pub fn sys_mutex_lock(mutex_id: usize) -> SysResult {
+    let tid = current_tid();
+    let mutex = with_current_process(|process| process.get_mutex(mutex_id))
+        .flatten()
+        .ok_or(SysError::EINVAL)?;
+    if mutex.owner() == Some(current_task_id()) {
+        return Err(SysError::EDEADLK);
+    }
+    // the check and the bookkeeping are done under the state of the mutex,
+    // so no other thread can take or be handed the lock in between
+    let result = mutex.lock_checked(true, |free| {
+        with_current_process(|process| {
+            let check = process.deadlock_detect;
+            let detector = &mut process.mutex_detector;
+            if !detector.request(tid, mutex_id, check) {
+                return false;
+            }
+            if free {
+                detector.acquire(tid, mutex_id);
+            }
+            true
+        }) == Some(true)
+    });
+    if result == Err(SysError::EINTR) {
+        with_current_process(|process| process.mutex_detector.cancel(tid, mutex_id));
+    }
+    result.map(|_| 0)
+}
+
+/// unlock mutex `mutex_id`, `EINVAL` if there is no such mutex, `EPERM` if the caller does not hold it
+pub fn sys_mutex_unlock(mutex_id: usize) -> SysResult {
+    let mutex = with_current_process(|process| process.get_mutex(mutex_id))
+        .flatten()
+        .ok_or(SysError::EINVAL)?;
+    if mutex.owner() != Some(current_task_id()) {
+        return Err(SysError::EPERM);
+    }
+    let tid = current_tid();
+    mutex.unlock_with(|next| mutex_handed_over(tid, mutex_id, next));
+    Ok(0)
+}
+
+/// Thread `tid` has unlocked mutex `mutex_id` and handed it over to task `next`, if any.
+fn mutex_handed_over(tid: usize, mutex_id: usize, next: Option<usize>) {
+    with_current_process(|process| {
+        process.mutex_detector.release(tid, mutex_id);
+        if let Some(next_tid) = next.and_then(|task| process.tid_of(task)) {
+            process.mutex_detector.acquire(next_tid, mutex_id);
+        }
+    });
+}
+
+/// create a semaphore with `res_count` resources in current process,
+/// return its id or `EINVAL` if the caller is not a user task
+pub fn sys_semaphore_create(res_count: usize) -> SysResult {
+    with_current_process(|process| {
+        process.add_semaphore(Semaphore::new(res_count), res_count) as isize
+    })
+    .ok_or(SysError::EINVAL)
+}
+
+/// release a resource of semaphore `sem_id`, `EINVAL` if there is no such semaphore
+pub fn sys_semaphore_up(sem_id: usize) -> SysResult {
+    let tid = current_tid();
+    let semaphore = with_current_process(|process| process.get_semaphore(sem_id))
+        .flatten()
+        .ok_or(SysError::EINVAL)?;
+    semaphore.up(|woken| {
+        with_current_process(|process| {
+            let detector = &mut process.semaphore_detector;
+            detector.release(tid, sem_id);
+            if let Some(woken_tid) = woken.and_then(|task| process.tid_of(task)) {
+                detector.acquire(woken_tid, sem_id);
+            }
+        });
+    });
+    Ok(0)
+}
+
+/// take a resource of semaphore `sem_id`, block until one is available,
+/// `EINVAL` if there is no such semaphore,
+/// `EDEADLK` if deadlock detection is on and waiting for it would be unsafe,
+/// `EINTR` if a signal interrupts the wait
+pub fn sys_semaphore_down(sem_id: usize) -> SysResult {
+    let tid = current_tid();
+    let semaphore = with_current_process(|process| process.get_semaphore(sem_id))
+        .flatten()
+        .ok_or(SysError::EINVAL)?;
+    // checked and booked under the count, like `sys_mutex_lock`
+    let result = semaphore.down(|available| {
+        with_current_process(|process| {
+            let check = process.deadlock_detect;
+            let detector = &mut process.semaphore_detector;
+            if !detector.request(tid, sem_id, check) {
+                return false;
+            }
+            if available {
+                detector.acquire(tid, sem_id);
+            }
+            true
+        }) == Some(true)
+    });
+    if result == Err(SysError::EINTR) {
+        with_current_process(|process| process.semaphore_detector.cancel(tid, sem_id));
+    }
+    result.map(|_| 0)
+}
+
+/// create a condition variable in current process,
+/// return its id or `EINVAL` if the caller is not a user task
+pub fn sys_condvar_create() -> SysResult {
+    with_current_process(|process| process.add_condvar(Condvar::new()) as isize)
+        .ok_or(SysError::EINVAL)
+}
+
+/// wake up a task waiting on condition variable `condvar_id`,
+/// `EINVAL` if there is no such condition variable
+pub fn sys_condvar_signal(condvar_id: usize) -> SysResult {
+    let condvar = with_current_process(|process| process.get_condvar(condvar_id))
+        .flatten()
+        .ok_or(SysError::EINVAL)?;
+    condvar.signal();
+    Ok(0)
+}
+
+/// wake up all tasks waiting on condition variable `condvar_id`,
+/// `EINVAL` if there is no such condition variable
+pub fn sys_condvar_broadcast(condvar_id: usize) -> SysResult {
+    let condvar = with_current_process(|process| process.get_condvar(condvar_id))
+        .flatten()
+        .ok_or(SysError::EINVAL)?;
+    condvar.broadcast();
+    Ok(0)
+}
+
+/// release mutex `mutex_id` and wait on condition variable `condvar_id`, then lock the mutex again,
+/// `EINVAL` if either does not exist, `EPERM` if the caller does not hold the mutex,
+/// `EINTR` if a signal interrupts the wait, the mutex is locked again all the same
+pub fn sys_condvar_wait(condvar_id: usize, mutex_id: usize) -> SysResult {
+    let (condvar, mutex) = with_current_process(|process| {
+        Some((
+            process.get_condvar(condvar_id)?,
+            process.get_mutex(mutex_id)?,
+        ))
+    })
+    .flatten()
+    .ok_or(SysError::EINVAL)?;
+    if mutex.owner() != Some(current_task_id()) {
+        return Err(SysError::EPERM);
+    }
+    let tid = current_tid();
+    let signaled =
+        condvar.wait(|| mutex.unlock_with(|next| mutex_handed_over(tid, mutex_id, next)));
+    // the mutex is taken back unconditionally, neither refused nor interrupted
+    let relocked = mutex.lock_checked(false, |free| {
+        with_current_process(|process| {
+            process.mutex_detector.request(tid, mutex_id, false);
+            if free {
+                process.mutex_detector.acquire(tid, mutex_id);
+            }
+        });
+        true
+    });
+    debug_assert!(relocked.is_ok());
+    if !signaled {
+        return Err(SysError::EINTR);
+    }
+    Ok(0)
+}
+
+/// turn deadlock detection of current process on (1) or off (0),
+/// `EINVAL` if `enabled` is neither or the caller is not a user task
+pub fn sys_enable_deadlock_detect(enabled: usize) -> SysResult {
+    if enabled > 1 {
+        return Err(SysError::EINVAL);
+    }
+    with_current_process(|process| process.deadlock_detect = enabled == 1)
+        .map(|_| 0)
+        .ok_or(SysError::EINVAL)
+}
+
+/// `FUTEX_WAIT`: block until woken if the u32 at `uaddr` still equals `val`,
+/// return 0 when woken, `EAGAIN` if the value has changed or `EINTR` if a signal comes first;
+/// `FUTEX_WAKE`: wake up at most `val` tasks waiting on `uaddr`, return how many were woken.
+///
+/// `EINVAL` if `uaddr` is misaligned or `op` is unknown, `EFAULT` if `uaddr` is unmapped.
+pub fn sys_futex(uaddr: usize, op: usize, val: usize) -> SysResult {
+    if uaddr % 4 != 0 {
+        return Err(SysError::EINVAL);
+    }
+    let pa = translate_user_addr(uaddr, false).ok_or(SysError::EFAULT)?;
+    match op {
+        FUTEX_WAIT => futex_wait(pa, val as u32),
+        FUTEX_WAKE => Ok(futex_wake(pa, val) as isize),
+        _ => Err(SysError::EINVAL),
+    }
+}
@@ -0,0 +1,35 @@
+use super::{SysError, SysResult};
+use crate::mm::{translated_byte_buffer, write_user};
+use crate::task::{create_thread, current_tid, waittid};
+
+/// create a thread in current process starting at `entry` with `arg` in a0,
+/// return its tid or `EINVAL` if the caller is not a user task
+///
+/// The thread function must end with `sys_exit`, there is nowhere to return to.
+pub fn sys_thread_create(entry: usize, arg: usize) -> SysResult {
+    create_thread(entry, arg)
+}
+
+/// get tid of current thread
+pub fn sys_gettid() -> SysResult {
+    Ok(current_tid() as isize)
+}
+
+/// reclaim an exited thread of current process, store its exit code into `exit_code` if not null
+/// and return `tid`, block until it exits if it is still running,
+/// `ECHILD` if there is no such thread, `EFAULT` if `exit_code` is not writable,
+/// `EINTR` if a signal interrupts the wait
+///
+/// The exit code is not returned in a0, where a negative one would look like an error.
+pub fn sys_waittid(tid: usize, exit_code: usize) -> SysResult {
+    // checked before the thread is reclaimed, so its exit code is not lost
+    let size = core::mem::size_of::<i32>();
+    if exit_code != 0 && translated_byte_buffer(exit_code, size, true).is_none() {
+        return Err(SysError::EFAULT);
+    }
+    let code = waittid(tid)? as i32;
+    if exit_code != 0 {
+        write_user(exit_code, &code).ok_or(SysError::EFAULT)?;
+    }
+    Ok(tid as isize)
+}
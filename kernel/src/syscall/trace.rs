@@ -0,0 +1,135 @@
+use super::*;
+use crate::println;
+use crate::task::set_trace_mask;
+use crate::timer::time_to_us;
+use alloc::string::String;
+use core::fmt::Write;
+
+/// Syscalls are traced by class, one bit per syscall module.
+pub const TRACE_FS: usize = 1 << 0;
+pub const TRACE_PROCESS: usize = 1 << 1;
+pub const TRACE_SIGNAL: usize = 1 << 2;
+pub const TRACE_SYNC: usize = 1 << 3;
+pub const TRACE_THREAD: usize = 1 << 4;
+pub const TRACE_ALL: usize = (1 << 5) - 1;
+
+/// Name, class and argument names of a syscall, `None` if unknown.
+fn syscall_desc(syscall_id: usize) -> Option<(&'static str, usize, &'static [&'static str])> {
+    let desc: (&'static str, usize, &'static [&'static str]) = match syscall_id {
+        SYSCALL_DUP => ("dup", TRACE_FS, &["fd"]),
+        SYSCALL_OPENAT => ("openat", TRACE_FS, &["dirfd", "path", "flags", "mode"]),
+        SYSCALL_CLOSE => ("close", TRACE_FS, &["fd"]),
+        SYSCALL_READ => ("read", TRACE_FS, &["fd", "buf", "len"]),
+        SYSCALL_WRITE => ("write", TRACE_FS, &["fd", "buf", "len"]),
+        SYSCALL_WRITEV => ("writev", TRACE_FS, &["fd", "iov", "iovcnt"]),
+        SYSCALL_EXIT => ("exit", TRACE_PROCESS, &["code"]),
+        SYSCALL_EXIT_GROUP => ("exit_group", TRACE_PROCESS, &["code"]),
+        SYSCALL_SET_TID_ADDRESS => ("set_tid_address", TRACE_THREAD, &["tidptr"]),
+        SYSCALL_FUTEX => ("futex", TRACE_SYNC, &["uaddr", "op", "val"]),
+        SYSCALL_NANOSLEEP => ("nanosleep", TRACE_PROCESS, &["req"]),
+        SYSCALL_CLOCK_GETTIME => ("clock_gettime", TRACE_PROCESS, &["clock_id", "tp"]),
+        SYSCALL_YIELD => ("yield", TRACE_PROCESS, &[]),
+        SYSCALL_KILL => ("kill", TRACE_SIGNAL, &["pid", "signum"]),
+        SYSCALL_SIGACTION => (
+            "sigaction",
+            TRACE_SIGNAL,
+            &["signum", "action", "old_action"],
+        ),
+        SYSCALL_SIGPROCMASK => ("sigprocmask", TRACE_SIGNAL, &["mask"]),
+        SYSCALL_SIGRETURN => ("sigreturn", TRACE_SIGNAL, &[]),
+        SYSCALL_SET_PRIORITY => ("set_priority", TRACE_PROCESS, &["nice"]),
+        SYSCALL_SETRLIMIT => ("setrlimit", TRACE_PROCESS, &["resource", "limit"]),
+        SYSCALL_GET_TIME => ("get_time", TRACE_PROCESS, &[]),
+        SYSCALL_BRK => ("brk", TRACE_PROCESS, &["addr"]),
+        SYSCALL_MMAP => (
+            "mmap",
+            TRACE_PROCESS,
+            &["addr", "len", "prot", "flags", "fd", "offset"],
+        ),
+        SYSCALL_SCHED_SETATTR => (
+            "sched_setattr",
+            TRACE_PROCESS,
+            &["runtime", "deadline", "period"],
+        ),
+        SYSCALL_TASK_INFO => ("task_info", TRACE_PROCESS, &["info"]),
+        SYSCALL_ENABLE_DEADLOCK_DETECT => ("enable_deadlock_detect", TRACE_SYNC, &["enabled"]),
+        SYSCALL_TRACE => ("trace", TRACE_PROCESS, &["pid", "mask"]),
+        SYSCALL_THREAD_CREATE => ("thread_create", TRACE_THREAD, &["entry", "arg"]),
+        SYSCALL_GETTID => ("gettid", TRACE_THREAD, &[]),
+        SYSCALL_WAITTID => ("waittid", TRACE_THREAD, &["tid", "exit_code"]),
+        SYSCALL_MUTEX_CREATE => ("mutex_create", TRACE_SYNC, &[]),
+        SYSCALL_MUTEX_LOCK => ("mutex_lock", TRACE_SYNC, &["mutex_id"]),
+        SYSCALL_MUTEX_UNLOCK => ("mutex_unlock", TRACE_SYNC, &["mutex_id"]),
+        SYSCALL_SEMAPHORE_CREATE => ("semaphore_create", TRACE_SYNC, &["res_count"]),
+        SYSCALL_SEMAPHORE_UP => ("semaphore_up", TRACE_SYNC, &["sem_id"]),
+        SYSCALL_SEMAPHORE_DOWN => ("semaphore_down", TRACE_SYNC, &["sem_id"]),
+        SYSCALL_CONDVAR_CREATE => ("condvar_create", TRACE_SYNC, &[]),
+        SYSCALL_CONDVAR_SIGNAL => ("condvar_signal", TRACE_SYNC, &["condvar_id"]),
+        SYSCALL_CONDVAR_WAIT => ("condvar_wait", TRACE_SYNC, &["condvar_id", "mutex_id"]),
+        SYSCALL_CONDVAR_BROADCAST => ("condvar_broadcast", TRACE_SYNC, &["condvar_id"]),
+        _ => return None,
+    };
+    Some(desc)
+}
+
+/// Whether `syscall_id` is traced under `mask`, unknown syscalls are traced by any mask.
+pub fn is_traced(syscall_id: usize, mask: usize) -> bool {
+    match syscall_desc(syscall_id) {
+        Some((_, class, _)) => mask & class != 0,
+        None => mask != 0,
+    }
+}
+
+/// Log a syscall of task `task_id` as it returns, `result` is `None` for syscalls which
+/// never return, logged before they run; `elapsed` is in timer cycles.
+pub fn log_syscall(
+    task_id: usize,
+    syscall_id: usize,
+    args: [usize; 6],
+    result: Option<SysResult>,
+    elapsed: usize,
+) {
+    let (name, _, arg_names) = syscall_desc(syscall_id).unwrap_or(("unknown", 0, &[]));
+    // format the whole line first, so lines of different harts do not interleave
+    let mut line = String::new();
+    write!(line, "[strace] task {} {}#{}(", task_id, name, syscall_id).unwrap();
+    for (i, (arg_name, arg)) in arg_names.iter().zip(args.iter()).enumerate() {
+        if i > 0 {
+            line.push_str(", ");
+        }
+        write!(line, "{}={:#x}", arg_name, arg).unwrap();
+    }
+    match result {
+        Some(Ok(ret)) => write!(line, ") = {} <{}us>", ret, time_to_us(elapsed)).unwrap(),
+        Some(Err(err)) => write!(
+            line,
+            ") = -{} {:?} <{}us>",
+            err.errno(),
+            err,
+            time_to_us(elapsed)
+        )
+        .unwrap(),
+        None => line.push_str(") = ?"),
+    }
+    println!("{}", line);
+}
+
+/// Syscall classes traced for every app from boot, a build flag rather than a boot option:
+/// the kernel is built with `STRACE=<mask>` in the environment, where the mask is decimal,
+/// hex with `0x`, or `all`, and changing it needs a rebuild.
+pub fn build_trace_mask() -> usize {
+    match option_env!("STRACE") {
+        None => 0,
+        Some("all") => TRACE_ALL,
+        Some(mask) => match mask.strip_prefix("0x") {
+            Some(hex) => usize::from_str_radix(hex, 16).unwrap_or(0),
+            None => mask.parse().unwrap_or(0),
+        },
+    }
+}
+
+/// trace syscalls of classes in `mask` made by all threads of process `pid`, 0 stops tracing,
+/// `ESRCH` if there is no such process
+pub fn sys_trace(pid: usize, mask: usize) -> SysResult {
+    set_trace_mask(pid, mask)
+}
@@ -0,0 +1,68 @@
+use super::scheduler::Scheduler;
+use super::task::TaskControlBlock;
+use crate::config::CLOCK_FREQ;
+use alloc::collections::BTreeSet;
+
+pub const NICE_MIN: isize = -20;
+pub const NICE_MAX: isize = 19;
+
+/// Weight of a task with nice value 0, vruntime advances at wall-clock speed for it.
+pub const NICE_0_WEIGHT: usize = 1024;
+
+/// A task runs at least 1ms (in vruntime) ahead of the leftmost task before a tick preempts it.
+const MIN_GRANULARITY: usize = CLOCK_FREQ / 1000;
+
+/// Weight of nice -20..=19, one nice level is roughly 10% cpu share (same table as linux).
+#[rustfmt::skip]
+const NICE_TO_WEIGHT: [usize; 40] = [
+    88761, 71755, 56483, 46273, 36291,
+    29154, 23254, 18705, 14949, 11916,
+    9548, 7620, 6100, 4904, 3906,
+    3121, 2501, 1991, 1586, 1277,
+    1024, 820, 655, 526, 423,
+    335, 272, 215, 172, 137,
+    110, 87, 70, 56, 45,
+    36, 29, 23, 18, 15,
+];
+
+pub fn nice_to_weight(nice: isize) -> usize {
+    NICE_TO_WEIGHT[(nice - NICE_MIN) as usize]
+}
+
+/// Completely fair scheduler: always run the `Ready` task with the smallest virtual runtime.
+pub struct CfsScheduler {
+    /// ready tasks ordered by (vruntime, task id)
+    timeline: BTreeSet<(usize, usize)>,
+    /// monotonic increasing vruntime of the last picked task
+    min_vruntime: usize,
+}
+
+impl Scheduler for CfsScheduler {
+    fn new() -> Self {
+        Self {
+            timeline: BTreeSet::new(),
+            min_vruntime: 0,
+        }
+    }
+
+    fn push(&mut self, id: usize, task: &mut TaskControlBlock) {
+        // a task which has not been runnable for a while must not monopolize the cpu
+        task.vruntime = task.vruntime.max(self.min_vruntime);
+        self.timeline.insert((task.vruntime, id));
+    }
+
+    fn pop(&mut self) -> Option<usize> {
+        let leftmost = *self.timeline.iter().next()?;
+        self.timeline.remove(&leftmost);
+        let (vruntime, id) = leftmost;
+        self.min_vruntime = self.min_vruntime.max(vruntime);
+        Some(id)
+    }
+
+    fn should_preempt(&self, current: &TaskControlBlock) -> bool {
+        match self.timeline.iter().next() {
+            Some(&(vruntime, _)) => current.vruntime > vruntime + MIN_GRANULARITY,
+            None => false,
+        }
+    }
+}
@@ -1,3 +1,5 @@
+use super::kthread::kthread_trampoline;
+
 #[derive(Copy, Clone)]
 #[repr(C)]
 pub struct TaskContext {
@@ -14,12 +16,20 @@ impl TaskContext {
             s: [0; 12],
         }
     }
+    /// Enter user mode with the `TrapContext` at `kstack_ptr` when first switched to.
     pub fn goto_restore(kstack_ptr: usize) -> Self {
         extern "C" {
-            fn __restore();
+            fn __enter_user();
+        }
+        Self {
+            ra: __enter_user as usize,
+            sp: kstack_ptr,
+            s: [0; 12],
         }
+    }
+    pub fn goto_kthread(kstack_ptr: usize) -> Self {
         Self {
-            ra: __restore as usize,
+            ra: kthread_trampoline as usize,
             sp: kstack_ptr,
             s: [0; 12],
         }
@@ -0,0 +1,128 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Resource bookkeeping of a kind of synchronization objects in a process for the banker's
+/// algorithm, threads are indexed by tid and resources by object id.
+pub struct DeadlockDetector {
+    /// free units of each resource
+    available: Vec<usize>,
+    /// units of each resource held by each thread
+    allocation: Vec<Vec<usize>>,
+    /// units of each resource each thread is waiting for
+    need: Vec<Vec<usize>>,
+}
+
+impl DeadlockDetector {
+    pub fn new() -> Self {
+        Self {
+            available: Vec::new(),
+            allocation: Vec::new(),
+            need: Vec::new(),
+        }
+    }
+
+    /// Resource `res` is created with `count` units, or reuses a freed id.
+    pub fn add_resource(&mut self, res: usize, count: usize) {
+        if res >= self.available.len() {
+            self.available.resize(res + 1, 0);
+        }
+        self.available[res] = count;
+    }
+
+    /// Grow the matrices to cover thread `tid` and resource `res`.
+    fn ensure(&mut self, tid: usize, res: usize) {
+        let num_res = self.available.len().max(res + 1);
+        let num_threads = self.allocation.len().max(tid + 1);
+        self.available.resize(num_res, 0);
+        self.allocation.resize(num_threads, vec![0; num_res]);
+        self.need.resize(num_threads, vec![0; num_res]);
+        for row in self.allocation.iter_mut().chain(self.need.iter_mut()) {
+            row.resize(num_res, 0);
+        }
+    }
+
+    /// Thread `tid` asks for a unit of resource `res`, followed by `acquire` once it gets it.
+    ///
+    /// If `check` is set, the request is refused and false is returned when waiting for it
+    /// would leave the process in an unsafe state.
+    pub fn request(&mut self, tid: usize, res: usize, check: bool) -> bool {
+        self.ensure(tid, res);
+        self.need[tid][res] += 1;
+        if check && !self.is_safe() {
+            self.need[tid][res] -= 1;
+            return false;
+        }
+        true
+    }
+
+    /// Thread `tid` has got the unit of resource `res` it asked for.
+    pub fn acquire(&mut self, tid: usize, res: usize) {
+        self.ensure(tid, res);
+        let need = self.need[tid][res].checked_sub(1);
+        let available = self.available[res].checked_sub(1);
+        debug_assert!(
+            need.is_some() && available.is_some(),
+            "Thread {} got resource {} which it did not ask for or is not free",
+            tid,
+            res
+        );
+        if let (Some(need), Some(available)) = (need, available) {
+            self.need[tid][res] = need;
+            self.available[res] = available;
+            self.allocation[tid][res] += 1;
+        }
+    }
+
+    /// Thread `tid` stops waiting for the unit of resource `res` it asked for.
+    pub fn cancel(&mut self, tid: usize, res: usize) {
+        self.ensure(tid, res);
+        if self.need[tid][res] > 0 {
+            self.need[tid][res] -= 1;
+        }
+    }
+
+    /// Thread `tid` gives back a unit of resource `res`, which it may not hold,
+    /// e.g. the producer of a semaphore.
+    pub fn release(&mut self, tid: usize, res: usize) {
+        self.ensure(tid, res);
+        if self.allocation[tid][res] > 0 {
+            self.allocation[tid][res] -= 1;
+        }
+        self.available[res] += 1;
+    }
+
+    /// Thread `tid` has exited, it neither waits for nor gives back anything any more.
+    ///
+    /// Units it still holds are not made free, as the objects they belong to never get them back.
+    pub fn remove_thread(&mut self, tid: usize) {
+        if tid < self.allocation.len() {
+            self.allocation[tid].fill(0);
+            self.need[tid].fill(0);
+        }
+    }
+
+    /// Whether all threads can finish in some order, each using the free units and
+    /// those given back by the threads finished before it.
+    fn is_safe(&self) -> bool {
+        let mut work = self.available.clone();
+        let mut finish = vec![false; self.need.len()];
+        loop {
+            let next = (0..finish.len()).find(|&tid| {
+                !finish[tid]
+                    && self.need[tid]
+                        .iter()
+                        .zip(work.iter())
+                        .all(|(need, work)| need <= work)
+            });
+            match next {
+                Some(tid) => {
+                    for (work, allocation) in work.iter_mut().zip(self.allocation[tid].iter()) {
+                        *work += allocation;
+                    }
+                    finish[tid] = true;
+                }
+                None => return finish.iter().all(|finished| *finished),
+            }
+        }
+    }
+}
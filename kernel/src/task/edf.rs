@@ -0,0 +1,139 @@
+use super::list::TaskList;
+use super::task::TaskControlBlock;
+use alloc::collections::BTreeSet;
+
+/// Fixed-point unit of cpu bandwidth, `BW_UNIT` means a whole cpu.
+const BW_SHIFT: usize = 20;
+const BW_UNIT: usize = 1 << BW_SHIFT;
+
+/// Parameters and current period state of a periodic real-time task, all in timer cycles.
+#[derive(Copy, Clone, Debug)]
+pub struct DeadlineEntity {
+    /// budget granted in every period
+    pub runtime: usize,
+    /// relative deadline to the start of each period
+    pub deadline: usize,
+    /// length of a period
+    pub period: usize,
+    /// deadline of the current job
+    pub abs_deadline: usize,
+    /// budget left in the current period, the task is throttled when it reaches 0
+    pub budget: usize,
+}
+
+impl DeadlineEntity {
+    pub fn new(runtime: usize, deadline: usize, period: usize, now: usize) -> Self {
+        Self {
+            runtime,
+            deadline,
+            period,
+            abs_deadline: now + deadline,
+            budget: runtime,
+        }
+    }
+
+    /// cpu share reserved for this task in `BW_UNIT`, i.e. its density `runtime / deadline`,
+    /// which is its utilization if the deadline equals the period
+    ///
+    /// Utilization alone is not enough for EDF when the deadline is shorter than the period.
+    pub fn bandwidth(&self) -> usize {
+        (((self.runtime as u128) << BW_SHIFT) / self.deadline as u128) as usize
+    }
+
+    /// A task waking up at `now` keeps its current job only if the budget left can still be
+    /// used up before the deadline, otherwise it starts a new period, so that a job woken up
+    /// too late does not outrank tasks with valid deadlines.
+    pub fn wake_up(&mut self, now: usize) {
+        if now + self.budget > self.abs_deadline {
+            self.replenish(now);
+        }
+    }
+
+    /// start of the next period
+    fn next_release(&self) -> usize {
+        self.abs_deadline - self.deadline + self.period
+    }
+
+    /// Refill the budget for the period starting at `release`.
+    fn replenish(&mut self, release: usize) {
+        self.abs_deadline = release + self.deadline;
+        self.budget = self.runtime;
+    }
+}
+
+/// Earliest deadline first scheduling class, always runs before the fair class.
+pub struct EdfScheduler {
+    /// runnable tasks ordered by (absolute deadline, task id)
+    ready: BTreeSet<(usize, usize)>,
+    /// tasks which used up their budget, ordered by (next release time, task id)
+    throttled: BTreeSet<(usize, usize)>,
+    /// sum of bandwidth of all admitted tasks, see `DeadlineEntity::bandwidth`
+    total_bw: usize,
+}
+
+impl EdfScheduler {
+    pub fn new() -> Self {
+        Self {
+            ready: BTreeSet::new(),
+            throttled: BTreeSet::new(),
+            total_bw: 0,
+        }
+    }
+
+    /// Admission control: accept `new` (replacing `old`) only if total density stays <= 1.
+    pub fn admit(&mut self, old: Option<&DeadlineEntity>, new: &DeadlineEntity) -> bool {
+        let total_bw = self.total_bw - old.map_or(0, |dl| dl.bandwidth());
+        if total_bw + new.bandwidth() > BW_UNIT {
+            return false;
+        }
+        self.total_bw = total_bw + new.bandwidth();
+        true
+    }
+
+    /// Give back the bandwidth of a task which left the real-time class or exited.
+    pub fn release(&mut self, dl: &DeadlineEntity) {
+        self.total_bw -= dl.bandwidth();
+    }
+
+    /// Put a `Ready` real-time task into run queue, or wait for next period if out of budget.
+    pub fn push(&mut self, id: usize, task: &TaskControlBlock) {
+        let dl = task.dl.as_ref().unwrap();
+        if dl.budget > 0 {
+            self.ready.insert((dl.abs_deadline, id));
+        } else {
+            self.throttled.insert((dl.next_release(), id));
+        }
+    }
+
+    pub fn pop(&mut self) -> Option<usize> {
+        let earliest = *self.ready.iter().next()?;
+        self.ready.remove(&earliest);
+        Some(earliest.1)
+    }
+
+    pub fn earliest_deadline(&self) -> Option<usize> {
+        self.ready.iter().next().map(|&(deadline, _)| deadline)
+    }
+
+    /// Move throttled tasks whose new period has begun back to run queue.
+    pub fn replenish(&mut self, now: usize, tasks: &mut TaskList) {
+        while let Some(&(release, id)) = self.throttled.iter().next() {
+            if release > now {
+                break;
+            }
+            self.throttled.remove(&(release, id));
+            // a thread killed with its process may have been reclaimed
+            let dl = match tasks.get_mut(id).and_then(|task| task.dl.as_mut()) {
+                Some(dl) => dl,
+                None => continue,
+            };
+            // a release missed by more than a period starts a new period right now
+            dl.replenish(if now - release >= dl.period {
+                now
+            } else {
+                release
+            });
+            self.ready.insert((dl.abs_deadline, id));
+        }
+    }
+}
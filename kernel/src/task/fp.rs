@@ -0,0 +1,139 @@
+use core::arch::asm;
+
+/// Floating-point registers of a user task.
+///
+/// Saved only when the task has dirtied them (`sstatus.FS` is `Dirty`) and restored only when
+/// another task has used the fp registers of the hart meanwhile, see `TaskManager::current_trap_exit`.
+#[derive(Copy, Clone)]
+#[repr(C)]
+pub struct FpContext {
+    f: [u64; 32],
+    fcsr: usize,
+}
+
+impl FpContext {
+    pub fn zero_init() -> Self {
+        Self {
+            f: [0; 32],
+            fcsr: 0,
+        }
+    }
+
+    /// Save the fp registers of current hart, `sstatus.FS` of the hart must not be `Off`.
+    pub fn save(&mut self) {
+        unsafe {
+            asm!(
+            "fsd f0, 0*8({0})",
+            "fsd f1, 1*8({0})",
+            "fsd f2, 2*8({0})",
+            "fsd f3, 3*8({0})",
+            "fsd f4, 4*8({0})",
+            "fsd f5, 5*8({0})",
+            "fsd f6, 6*8({0})",
+            "fsd f7, 7*8({0})",
+            "fsd f8, 8*8({0})",
+            "fsd f9, 9*8({0})",
+            "fsd f10, 10*8({0})",
+            "fsd f11, 11*8({0})",
+            "fsd f12, 12*8({0})",
+            "fsd f13, 13*8({0})",
+            "fsd f14, 14*8({0})",
+            "fsd f15, 15*8({0})",
+            "fsd f16, 16*8({0})",
+            "fsd f17, 17*8({0})",
+            "fsd f18, 18*8({0})",
+            "fsd f19, 19*8({0})",
+            "fsd f20, 20*8({0})",
+            "fsd f21, 21*8({0})",
+            "fsd f22, 22*8({0})",
+            "fsd f23, 23*8({0})",
+            "fsd f24, 24*8({0})",
+            "fsd f25, 25*8({0})",
+            "fsd f26, 26*8({0})",
+            "fsd f27, 27*8({0})",
+            "fsd f28, 28*8({0})",
+            "fsd f29, 29*8({0})",
+            "fsd f30, 30*8({0})",
+            "fsd f31, 31*8({0})",
+            "frcsr {1}",
+            in(reg) self.f.as_mut_ptr(),
+            out(reg) self.fcsr,
+            );
+        }
+    }
+
+    /// Load the fp registers of current hart, `sstatus.FS` of the hart must not be `Off`.
+    pub fn restore(&self) {
+        unsafe {
+            asm!(
+            "fld f0, 0*8({0})",
+            "fld f1, 1*8({0})",
+            "fld f2, 2*8({0})",
+            "fld f3, 3*8({0})",
+            "fld f4, 4*8({0})",
+            "fld f5, 5*8({0})",
+            "fld f6, 6*8({0})",
+            "fld f7, 7*8({0})",
+            "fld f8, 8*8({0})",
+            "fld f9, 9*8({0})",
+            "fld f10, 10*8({0})",
+            "fld f11, 11*8({0})",
+            "fld f12, 12*8({0})",
+            "fld f13, 13*8({0})",
+            "fld f14, 14*8({0})",
+            "fld f15, 15*8({0})",
+            "fld f16, 16*8({0})",
+            "fld f17, 17*8({0})",
+            "fld f18, 18*8({0})",
+            "fld f19, 19*8({0})",
+            "fld f20, 20*8({0})",
+            "fld f21, 21*8({0})",
+            "fld f22, 22*8({0})",
+            "fld f23, 23*8({0})",
+            "fld f24, 24*8({0})",
+            "fld f25, 25*8({0})",
+            "fld f26, 26*8({0})",
+            "fld f27, 27*8({0})",
+            "fld f28, 28*8({0})",
+            "fld f29, 29*8({0})",
+            "fld f30, 30*8({0})",
+            "fld f31, 31*8({0})",
+            "fscsr {1}",
+            in(reg) self.f.as_ptr(),
+            in(reg) self.fcsr,
+            out("f0") _,
+            out("f1") _,
+            out("f2") _,
+            out("f3") _,
+            out("f4") _,
+            out("f5") _,
+            out("f6") _,
+            out("f7") _,
+            out("f8") _,
+            out("f9") _,
+            out("f10") _,
+            out("f11") _,
+            out("f12") _,
+            out("f13") _,
+            out("f14") _,
+            out("f15") _,
+            out("f16") _,
+            out("f17") _,
+            out("f18") _,
+            out("f19") _,
+            out("f20") _,
+            out("f21") _,
+            out("f22") _,
+            out("f23") _,
+            out("f24") _,
+            out("f25") _,
+            out("f26") _,
+            out("f27") _,
+            out("f28") _,
+            out("f29") _,
+            out("f30") _,
+            out("f31") _,
+            );
+        }
+    }
+}
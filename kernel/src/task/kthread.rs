@@ -0,0 +1,35 @@
+use super::{exit_current_and_run_next, kthread_spawn, take_current_entry};
+use crate::println;
+use crate::sync::{SpinLock, WaitQueue};
+use alloc::sync::Arc;
+
+/// First code a kernel thread runs, `ra` of its initial `TaskContext` points here.
+///
+/// Kernel threads run with interrupts disabled and are never preempted,
+/// a long running one should call `suspend_current_and_run_next` now and then.
+pub extern "C" fn kthread_trampoline() -> ! {
+    let entry = take_current_entry();
+    entry();
+    exit_current_and_run_next(0);
+    panic!("Unreachable in kthread_trampoline!");
+}
+
+/// Check kernel threads at boot: one blocks on a `WaitQueue` until another hands it a result.
+pub fn kthread_self_test() {
+    let result = Arc::new(SpinLock::new(None));
+    let queue = Arc::new(WaitQueue::new());
+    let (waiter_result, waiter_queue) = (result.clone(), queue.clone());
+    kthread_spawn(move || {
+        let mut sum = waiter_result.lock();
+        while sum.is_none() {
+            waiter_queue.sleep_on_and_release(|| drop(sum));
+            sum = waiter_result.lock();
+        }
+        assert_eq!(*sum, Some(5050));
+        println!("[kernel] kthread self test passed");
+    });
+    kthread_spawn(move || {
+        *result.lock() = Some((1..=100).sum::<usize>());
+        queue.wake_all();
+    });
+}
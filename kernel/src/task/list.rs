@@ -0,0 +1,60 @@
+use super::task::TaskControlBlock;
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::ops::{Index, IndexMut};
+
+/// Task control blocks indexed by task id.
+///
+/// Boxed so that a `TaskContext` being switched without the lock held never moves.
+/// The slot of a reclaimed task stays empty and its id is never reused,
+/// as run queues, timers and wait queues may still hold the id.
+pub struct TaskList {
+    slots: Vec<Option<Box<TaskControlBlock>>>,
+}
+
+impl TaskList {
+    pub fn new() -> Self {
+        Self { slots: Vec::new() }
+    }
+
+    /// Add `task` and return its id.
+    pub fn push(&mut self, task: TaskControlBlock) -> usize {
+        self.slots.push(Some(Box::new(task)));
+        self.slots.len() - 1
+    }
+
+    /// Task `id`, `None` if it has been reclaimed.
+    pub fn get(&self, id: usize) -> Option<&TaskControlBlock> {
+        self.slots.get(id)?.as_deref()
+    }
+
+    pub fn get_mut(&mut self, id: usize) -> Option<&mut TaskControlBlock> {
+        self.slots.get_mut(id)?.as_deref_mut()
+    }
+
+    /// Free task `id` together with its stacks, it must not be on any hart.
+    pub fn remove(&mut self, id: usize) -> Option<Box<TaskControlBlock>> {
+        self.slots.get_mut(id)?.take()
+    }
+
+    /// The first `count` tasks, which are never reclaimed.
+    pub fn first(&self, count: usize) -> impl Iterator<Item = &TaskControlBlock> {
+        self.slots[..count]
+            .iter()
+            .map(|task| task.as_deref().unwrap())
+    }
+}
+
+impl Index<usize> for TaskList {
+    type Output = TaskControlBlock;
+
+    fn index(&self, id: usize) -> &TaskControlBlock {
+        self.get(id).expect("Task has been reclaimed!")
+    }
+}
+
+impl IndexMut<usize> for TaskList {
+    fn index_mut(&mut self, id: usize) -> &mut TaskControlBlock {
+        self.get_mut(id).expect("Task has been reclaimed!")
+    }
+}
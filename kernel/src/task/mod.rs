@@ -1,128 +1,898 @@
+mod cfs;
 mod context;
+mod deadlock;
+mod edf;
+mod fp;
+mod kthread;
+mod list;
+mod process;
+mod processor;
+mod scheduler;
+mod signal;
+mod stack;
 mod switch;
 #[allow(clippy::module_inception)]
 mod task;
 
-use crate::config::MAX_APP_NUM;
-use crate::loader::{get_num_app, init_app_cx};
-use crate::sync::UPSafeCell;
+use crate::config::MAX_HARTS;
+use crate::drivers::handle_external_interrupt;
+use crate::loader::{app_loaded, get_app_free_range, get_app_regions, get_num_app, init_app_cx};
+use crate::mm::translate_user_addr;
+use crate::riscv::{enable_fp, hart_id};
+use crate::sbi::send_ipi;
+use crate::sbi::shutdown;
+use crate::sync::{futex_wake, SpinLock, WaitQueue};
+use crate::syscall::{build_trace_mask, SysError, SysResult};
+use crate::timer::{
+    add_timer, check_timer, get_time, get_time_ms, remove_timer, set_next_trigger, us_to_time,
+};
+use crate::trap::TrapContext;
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use cfs::{CfsScheduler, NICE_MAX, NICE_MIN};
+use core::ops::Range;
+use edf::{DeadlineEntity, EdfScheduler};
 use lazy_static::*;
+use list::TaskList;
+use process::MAIN_TID;
+use processor::Processor;
+use riscv::register::sip;
+use riscv::register::sstatus::FS;
+use scheduler::Scheduler;
+use signal::{default_ignored, SignalFlags, MAX_SIG, SIG_DFL, SIG_IGN};
 use switch::__switch;
 use task::{TaskControlBlock, TaskStatus};
 
 use crate::println;
 pub use context::TaskContext;
+pub use kthread::kthread_self_test;
+pub use process::{PageCounter, ProcessControlBlock};
+pub use signal::{SignalAction, SIGBUS, SIGILL, SIGKILL, SIGSEGV, SIGTRAP};
+pub use task::TaskInfo;
+
+/// cpu time limit in milliseconds
+pub const RLIMIT_CPU: usize = 0;
+/// max pages of heap, anonymous mappings and page frames of the process
+pub const RLIMIT_AS: usize = 9;
+/// wall-clock limit since the first run in milliseconds
+pub const RLIMIT_WALL: usize = 16;
+/// no limit for the resource
+pub const RLIM_INFINITY: usize = usize::MAX;
+/// exit code of a task terminated by signal `signum`, the negative signal number
+pub const fn signal_exit_code(signum: usize) -> i32 {
+    -(signum as i32)
+}
+/// exit code of a task killed for exceeding its time limit, as if killed by `SIGXCPU`
+pub const EXIT_RLIMIT: i32 = signal_exit_code(signal::SIGXCPU);
+
+type SchedulerImpl = CfsScheduler;
 
 pub struct TaskManager {
-    /// total number of tasks
+    /// total number of apps, which are the first tasks in task list
     num_app: usize,
-    /// use inner value to get mutable access
-    inner: UPSafeCell<TaskManagerInner>,
+    /// use inner value to get mutable access, shared by all harts
+    inner: SpinLock<TaskManagerInner>,
 }
 
 struct TaskManagerInner {
-    /// task list
-    tasks: [TaskControlBlock; MAX_APP_NUM],
-    /// id of current `Running` task
-    current_task: usize,
+    /// task list, main threads of apps followed by kernel threads and user threads
+    tasks: TaskList,
+    /// process list indexed by pid, the main thread of process `i` is task `i`
+    processes: Vec<ProcessControlBlock>,
+    /// state of each hart, indexed by hart id
+    processors: Vec<Processor>,
+    /// run queue of `Ready` best-effort tasks
+    scheduler: SchedulerImpl,
+    /// run queue of `Ready` real-time tasks, always served before `scheduler`
+    rt_scheduler: EdfScheduler,
+}
+
+impl TaskManagerInner {
+    /// id of the task running on current hart
+    fn current_task(&self) -> usize {
+        self.processors[hart_id()].current.unwrap()
+    }
+
+    /// Whether task `id` is running on some hart, or has not switched back to its idle loop yet.
+    fn on_cpu(&self, id: usize) -> bool {
+        self.processors
+            .iter()
+            .any(|processor| processor.current == Some(id))
+    }
+
+    /// Whether task `id` has a pending signal which is neither blocked nor ignored,
+    /// so that it should not stay `Blocked` until the signal is handled.
+    fn has_interrupting_signal(&self, id: usize) -> bool {
+        let task = &self.tasks[id];
+        let pid = match task.pid {
+            Some(pid) => pid,
+            None => return false,
+        };
+        let in_handler = task.trap_cx_backup.is_some();
+        let pending = task.signals - task.signal_mask;
+        (1..=MAX_SIG)
+            .filter(|&signum| pending.contains(SignalFlags::from_signum(signum).unwrap()))
+            .any(
+                |signum| match self.processes[pid].signal_actions[signum].handler {
+                    SIG_IGN => false,
+                    SIG_DFL => !default_ignored(signum),
+                    // held pending until the running handler returns, see `take_current_signal`
+                    _ => !in_handler,
+                },
+            )
+    }
+
+    /// Wake up task `id` if it is `Blocked` interruptibly and a signal is to be handled,
+    /// its blocking call then fails with `EINTR`.
+    fn interrupt(&mut self, id: usize) {
+        let task = &self.tasks[id];
+        if task.task_status != TaskStatus::Blocked
+            || !task.interruptible
+            || !self.has_interrupting_signal(id)
+        {
+            return;
+        }
+        self.tasks[id].interrupted = true;
+        self.unblock(id);
+    }
+
+    /// Make `Blocked` task `id` `Ready` again.
+    fn unblock(&mut self, id: usize) {
+        let task = &mut self.tasks[id];
+        task.task_status = TaskStatus::Ready;
+        if let Some(dl) = task.dl.as_mut() {
+            dl.wake_up(get_time());
+        }
+        // a task blocked but not switched out yet is put back by the idle loop
+        if !self.on_cpu(id) {
+            self.push_ready(id);
+        }
+    }
+
+    /// Put a `Ready` task into the run queue of its scheduling class,
+    /// and wake up an idle hart to run it.
+    ///
+    /// Without an idle hart, a real-time task makes a hart running a best-effort task,
+    /// current hart included, reschedule at once rather than at its next tick.
+    fn push_ready(&mut self, id: usize) {
+        let task = &mut self.tasks[id];
+        let real_time = task.dl.is_some();
+        if real_time {
+            self.rt_scheduler.push(id, task);
+        } else {
+            self.scheduler.push(id, task);
+        }
+        let hart = hart_id();
+        let idle =
+            self.processors.iter().enumerate().find(|(i, processor)| {
+                *i != hart && processor.online && processor.current.is_none()
+            });
+        let target = idle.or_else(|| {
+            if !real_time {
+                return None;
+            }
+            self.processors.iter().enumerate().find(|(_, processor)| {
+                processor.online
+                    && processor
+                        .current
+                        .map_or(false, |current| self.tasks[current].dl.is_none())
+            })
+        });
+        if let Some((target, _)) = target {
+            send_ipi(1 << target);
+        }
+    }
+
+    /// Pick the next task to run, real-time tasks go before best-effort ones.
+    fn pick_next(&mut self) -> Option<usize> {
+        self.rt_scheduler.replenish(get_time(), &mut self.tasks);
+        // threads killed together with their process are left in run queues
+        while let Some(id) = self.rt_scheduler.pop().or_else(|| self.scheduler.pop()) {
+            if self.tasks.get(id).map(|task| task.task_status) == Some(TaskStatus::Ready) {
+                return Some(id);
+            }
+        }
+        None
+    }
+
+    /// Mark all threads of process `pid` except the current one as `Exited`.
+    ///
+    /// A thread running on another hart can not be stopped right away,
+    /// it gets `SIGKILL` and exits when it returns to user mode.
+    fn kill_other_threads(&mut self, pid: usize, exit_code: i32) {
+        let current = self.current_task();
+        let mut exited = Vec::new();
+        for id in self.processes[pid].thread_tasks() {
+            let on_cpu = self.on_cpu(id);
+            let task = &mut self.tasks[id];
+            match task.task_status {
+                TaskStatus::Exited => continue,
+                TaskStatus::Running => {
+                    if id != current {
+                        task.signals |= SignalFlags::SIGKILL;
+                    }
+                    continue;
+                }
+                _ => {}
+            }
+            task.task_status = TaskStatus::Exited;
+            task.exit_code = exit_code;
+            exited.push(task.tid);
+            if let Some(dl) = task.dl.as_ref() {
+                self.rt_scheduler.release(dl);
+            }
+            // a task still switching out on another hart is freed by the idle loop there
+            if !on_cpu {
+                task.release_stacks();
+            }
+        }
+        for tid in exited {
+            self.processes[pid].thread_exited(tid);
+        }
+    }
 }
 
 lazy_static! {
     pub static ref TASK_MANAGER: TaskManager = {
         let num_app = get_num_app();
-        let mut tasks = [TaskControlBlock {
-            task_cx: TaskContext::zero_init(),
-            task_status: TaskStatus::UnInit,
-        }; MAX_APP_NUM];
-        for (i, task) in tasks.iter_mut().enumerate() {
-            task.task_cx = TaskContext::goto_restore(init_app_cx(i));
+        let mut tasks = TaskList::new();
+        let mut processes = Vec::new();
+        let mut scheduler = SchedulerImpl::new();
+        for i in 0..num_app {
+            let mut process = ProcessControlBlock::new(i);
+            process.trace_mask = build_trace_mask();
+            // an app that could not be loaded exits at once with code -1
+            if !app_loaded(i) {
+                let mut task = TaskControlBlock::new(TaskContext::zero_init());
+                task.task_status = TaskStatus::Exited;
+                task.pid = Some(i);
+                task.tid = MAIN_TID;
+                task.exit_code = -1;
+                tasks.push(task);
+                processes.push(process);
+                continue;
+            }
+            let mut task = TaskControlBlock::new(TaskContext::goto_restore(init_app_cx(i)));
             task.task_status = TaskStatus::Ready;
+            task.pid = Some(i);
+            task.tid = MAIN_TID;
+            scheduler.push(i, &mut task);
             println!("app {} task info {}", i, task);
+            tasks.push(task);
+            let (free_start, free_end) = get_app_free_range(i);
+            process.init_free_memory(free_start, free_end);
+            processes.push(process);
         }
         TaskManager {
             num_app,
-            inner: unsafe {
-                UPSafeCell::new(TaskManagerInner {
-                    tasks,
-                    current_task: 0,
-                })
-            },
+            inner: SpinLock::new(TaskManagerInner {
+                tasks,
+                processes,
+                processors: (0..MAX_HARTS).map(|_| Processor::new()).collect(),
+                scheduler,
+                rt_scheduler: EdfScheduler::new(),
+            }),
         }
     };
 }
 
 impl TaskManager {
-    /// Run the first task in task list.
+    /// The idle loop of current hart, run on its boot stack.
     ///
-    /// Generally, the first task in task list is an idle task (we call it zero process later).
-    /// But in ch3, we load apps statically, so the first task is a real app.
-    fn run_first_task(&self) -> ! {
-        let mut inner = self.inner.exclusive_access();
-        let task0 = &mut inner.tasks[0];
-        task0.task_status = TaskStatus::Running;
-        let next_task_cx_ptr = &task0.task_cx as *const TaskContext;
-        drop(inner);
-        let mut _unused = TaskContext::zero_init();
-        // before this, we should drop local variables that must be dropped manually
-        unsafe {
-            __switch(&mut _unused as *mut TaskContext, next_task_cx_ptr);
+    /// Switch to the task picked by the scheduler and get back here whenever it gives up the cpu.
+    /// Wait for interrupt if no task is `Ready`, and shut down when all apps have exited,
+    /// kernel threads are not waited for.
+    ///
+    /// A task giving up the cpu is only put back to run queue here, after its context is saved,
+    /// so that no other hart can switch to it halfway.
+    fn run_tasks(&self) -> ! {
+        let hart = hart_id();
+        self.inner.lock().processors[hart].online = true;
+        loop {
+            let mut inner = self.inner.lock();
+            if let Some(next) = inner.pick_next() {
+                inner.tasks[next].switch_in(get_time());
+                inner.processors[hart].current = Some(next);
+                let idle_task_cx_ptr = &mut inner.processors[hart].idle_task_cx as *mut TaskContext;
+                let next_task_cx_ptr = &inner.tasks[next].task_cx as *const TaskContext;
+                drop(inner);
+                // before this, we should drop local variables that must be dropped manually
+                unsafe {
+                    __switch(idle_task_cx_ptr, next_task_cx_ptr);
+                }
+                let mut inner = self.inner.lock();
+                let current = inner.processors[hart].current.take().unwrap();
+                let status = inner.tasks[current].task_status;
+                match status {
+                    TaskStatus::Ready => inner.push_ready(current),
+                    // we are on the boot stack now, the kernel stack of the task is not in use
+                    TaskStatus::Exited => {
+                        inner.tasks[current].release_stacks();
+                        drop(inner);
+                        announce_task_exit();
+                    }
+                    _ => {}
+                }
+            } else if inner
+                .tasks
+                .first(self.num_app)
+                .all(|task| task.task_status == TaskStatus::Exited)
+            {
+                drop(inner);
+                self.shutdown();
+            } else {
+                drop(inner);
+                wait_for_interrupt();
+            }
+        }
+    }
+
+    /// Print a summary of all tasks and shut down, failed if any task exited with non-zero code.
+    fn shutdown(&self) -> ! {
+        let inner = self.inner.lock();
+        let failed = inner
+            .tasks
+            .first(self.num_app)
+            .filter(|task| task.exit_code != 0)
+            .count();
+        println!(
+            "[kernel] All {} applications completed in {}ms, {} failed",
+            self.num_app,
+            get_time_ms(),
+            failed
+        );
+        for (i, task) in inner.tasks.first(self.num_app).enumerate() {
+            println!("[kernel] app {} exited with code {}", i, task.exit_code);
         }
-        panic!("unreachable in run_first_task!");
+        drop(inner);
+        shutdown(failed != 0)
     }
 
-    /// Change the status of current `Running` task into `Ready`.
+    /// Change the status of current `Running` task into `Ready`,
+    /// it is put back to run queue by the idle loop.
     fn mark_current_suspended(&self) {
-        let mut inner = self.inner.exclusive_access();
-        let current = inner.current_task;
+        let mut inner = self.inner.lock();
+        let current = inner.current_task();
+        let now = get_time();
+        inner.tasks[current].update_runtime(now);
+        inner.tasks[current].kernel_time_end(now);
         inner.tasks[current].task_status = TaskStatus::Ready;
     }
 
+    /// Change the status of current `Running` task into `Blocked`, it is not in any run queue
+    /// until someone calls `wakeup_task` on it, or a signal interrupts it if `interruptible`.
+    ///
+    /// A signal which arrived before, e.g. while the task was running in the kernel,
+    /// interrupts it at once, it becomes `Ready` instead.
+    fn mark_current_blocked(&self, interruptible: bool) -> usize {
+        let mut inner = self.inner.lock();
+        let current = inner.current_task();
+        let now = get_time();
+        inner.tasks[current].update_runtime(now);
+        inner.tasks[current].kernel_time_end(now);
+        inner.tasks[current].interruptible = interruptible;
+        if interruptible && inner.has_interrupting_signal(current) {
+            inner.tasks[current].task_status = TaskStatus::Ready;
+            inner.tasks[current].interrupted = true;
+        } else {
+            inner.tasks[current].task_status = TaskStatus::Blocked;
+        }
+        current
+    }
+
+    /// Whether current task was woken up by a signal from its last block, clearing it.
+    fn take_current_interrupted(&self) -> bool {
+        let mut inner = self.inner.lock();
+        let current = inner.current_task();
+        core::mem::take(&mut inner.tasks[current].interrupted)
+    }
+
+    /// Put a `Blocked` task back to run queue, return false if it is not `Blocked`.
+    fn wakeup_task(&self, id: usize) -> bool {
+        let mut inner = self.inner.lock();
+        // the task may have exited and been reclaimed since it was put into a wait queue
+        if inner.tasks.get(id).map(|task| task.task_status) != Some(TaskStatus::Blocked) {
+            return false;
+        }
+        inner.unblock(id);
+        true
+    }
+
+    /// Current real-time task finished its job, throttle it until the next period.
+    fn mark_current_job_done(&self) {
+        let mut inner = self.inner.lock();
+        let current = inner.current_task();
+        if let Some(dl) = inner.tasks[current].dl.as_mut() {
+            dl.budget = 0;
+        }
+    }
+
     /// Change the status of current `Running` task into `Exited`.
-    fn mark_current_exited(&self) {
-        let mut inner = self.inner.exclusive_access();
-        let current = inner.current_task;
+    ///
+    /// When the main thread of a process exits, all other threads of the process exit too.
+    fn mark_current_exited(&self, exit_code: i32) {
+        let mut inner = self.inner.lock();
+        let current = inner.current_task();
+        let now = get_time();
+        inner.tasks[current].update_runtime(now);
+        inner.tasks[current].kernel_time_end(now);
         inner.tasks[current].task_status = TaskStatus::Exited;
+        inner.tasks[current].exit_code = exit_code;
+        if let Some(dl) = inner.tasks[current].dl.take() {
+            inner.rt_scheduler.release(&dl);
+        }
+        if let Some(pid) = inner.tasks[current].pid {
+            let tid = inner.tasks[current].tid;
+            inner.processes[pid].thread_exited(tid);
+            if tid == MAIN_TID {
+                inner.kill_other_threads(pid, exit_code);
+            }
+        }
+    }
+
+    /// Exit all threads of the process of current task, current task exits last.
+    fn mark_current_group_exited(&self, exit_code: i32) {
+        {
+            let mut inner = self.inner.lock();
+            let current = inner.current_task();
+            if let Some(pid) = inner.tasks[current].pid {
+                inner.kill_other_threads(pid, exit_code);
+            }
+        }
+        self.mark_current_exited(exit_code);
+    }
+
+    /// Set `clear_child_tid` of current task and return its tid.
+    fn set_current_clear_child_tid(&self, addr: usize) -> usize {
+        let mut inner = self.inner.lock();
+        let current = inner.current_task();
+        inner.tasks[current].clear_child_tid = addr;
+        inner.tasks[current].tid
+    }
+
+    fn take_current_clear_child_tid(&self) -> usize {
+        let mut inner = self.inner.lock();
+        let current = inner.current_task();
+        core::mem::take(&mut inner.tasks[current].clear_child_tid)
+    }
+
+    /// Create a thread in the process of current task and return its tid,
+    /// `EINVAL` for kernel threads.
+    fn create_thread(&self, entry: usize, arg: usize) -> SysResult {
+        let mut inner = self.inner.lock();
+        let current = inner.current_task();
+        let pid = inner.tasks[current].pid.ok_or(SysError::EINVAL)?;
+        let tid = inner.processes[pid].next_tid();
+        let mut task = TaskControlBlock::new_user_thread(pid, tid, entry, arg);
+        task.task_status = TaskStatus::Ready;
+        let id = inner.tasks.push(task);
+        inner.processes[pid].add_thread(id);
+        inner.push_ready(id);
+        Ok(tid as isize)
+    }
+
+    /// Run `f` on the process of current task, return `None` for kernel threads.
+    fn with_current_process<R>(&self, f: impl FnOnce(&mut ProcessControlBlock) -> R) -> Option<R> {
+        let mut inner = self.inner.lock();
+        let current = inner.current_task();
+        let pid = inner.tasks[current].pid?;
+        Some(f(&mut inner.processes[pid]))
+    }
+
+    fn current_task_id(&self) -> usize {
+        self.inner.lock().current_task()
+    }
+
+    fn try_current_task_id(&self) -> Option<usize> {
+        self.inner.lock().processors[hart_id()].current
+    }
+
+    fn current_tid(&self) -> usize {
+        let inner = self.inner.lock();
+        inner.tasks[inner.current_task()].tid
+    }
+
+    /// Reclaim thread `tid` of current process and return its exit code, the control block
+    /// and stacks of the thread are freed.
+    ///
+    /// `ECHILD` if there is no such thread, or it is the caller or the main thread,
+    /// `EAGAIN` if it has not exited yet.
+    fn waittid(&self, tid: usize) -> SysResult {
+        let mut inner = self.inner.lock();
+        let current = inner.current_task();
+        let pid = inner.tasks[current].pid.ok_or(SysError::ECHILD)?;
+        // the main thread holds the pid, it is never reclaimed
+        if inner.tasks[current].tid == tid || tid == MAIN_TID {
+            return Err(SysError::ECHILD);
+        }
+        let id = match inner.processes[pid].threads.get(tid) {
+            Some(Some(id)) => *id,
+            _ => return Err(SysError::ECHILD),
+        };
+        // an exited thread may still be switching out on another hart, using its kernel stack
+        if inner.tasks[id].task_status != TaskStatus::Exited || inner.on_cpu(id) {
+            return Err(SysError::EAGAIN);
+        }
+        inner.processes[pid].threads[tid] = None;
+        let task = inner.tasks.remove(id).unwrap();
+        Ok(task.exit_code as isize)
+    }
+
+    fn set_trace_mask(&self, pid: usize, mask: usize) -> SysResult {
+        let mut inner = self.inner.lock();
+        let process = inner.processes.get_mut(pid).ok_or(SysError::ESRCH)?;
+        process.trace_mask = mask;
+        Ok(0)
+    }
+
+    /// Send signal `signum` to process `pid`, it is handled by the main thread.
+    ///
+    /// The signal is delivered when the main thread next returns to user mode,
+    /// a `Blocked` one is not woken up for it.
+    fn kill(&self, pid: usize, signum: usize) -> SysResult {
+        let mut inner = self.inner.lock();
+        let signal = SignalFlags::from_signum(signum).ok_or(SysError::EINVAL)?;
+        if pid >= inner.processes.len() || inner.tasks[pid].task_status == TaskStatus::Exited {
+            return Err(SysError::ESRCH);
+        }
+        inner.tasks[pid].signals |= signal;
+        inner.interrupt(pid);
+        Ok(0)
+    }
+
+    /// Raise `signum` on current task for a fault it caused, like `force_sig` of Linux.
+    ///
+    /// Returning to the faulting instruction would fault again, so the signal must not stay
+    /// pending: it is unblocked, and reset to `SIG_DFL` if it is ignored, or if its handler can
+    /// not run now as another handler is running, so that the task is terminated instead.
+    fn force_current_signal(&self, signum: usize) {
+        let mut inner = self.inner.lock();
+        let current = inner.current_task();
+        let signal = SignalFlags::from_signum(signum).unwrap();
+        let task = &mut inner.tasks[current];
+        task.signals |= signal;
+        task.signal_mask -= signal;
+        let in_handler = task.trap_cx_backup.is_some();
+        let pid = match task.pid {
+            Some(pid) => pid,
+            None => return,
+        };
+        let action = &mut inner.processes[pid].signal_actions[signum];
+        if action.handler == SIG_IGN || (action.handler != SIG_DFL && in_handler) {
+            action.handler = SIG_DFL;
+        }
+    }
+
+    /// Install `action` for `signum` in current process if given, return the old one.
+    ///
+    /// Return `None` for kernel threads, invalid signals, or signals which can not be caught.
+    fn set_current_sigaction(
+        &self,
+        signum: usize,
+        action: Option<SignalAction>,
+    ) -> Option<SignalAction> {
+        let mut inner = self.inner.lock();
+        let current = inner.current_task();
+        let pid = inner.tasks[current].pid?;
+        let signal = SignalFlags::from_signum(signum)?;
+        if SignalFlags::unmaskable().contains(signal) {
+            return None;
+        }
+        let old = inner.processes[pid].signal_actions[signum];
+        if let Some(action) = action {
+            inner.processes[pid].signal_actions[signum] = action;
+        }
+        Some(old)
+    }
+
+    /// Set blocked signals of current task, return the old mask.
+    fn set_current_sigmask(&self, mask: SignalFlags) -> SignalFlags {
+        let mut inner = self.inner.lock();
+        let current = inner.current_task();
+        let old = inner.tasks[current].signal_mask;
+        inner.tasks[current].signal_mask = mask - SignalFlags::unmaskable();
+        old
+    }
+
+    /// Take the lowest pending signal of current task which can be delivered now,
+    /// together with its action.
+    ///
+    /// Handlers do not nest, a signal caught by a user handler waits until `sys_sigreturn`.
+    fn take_current_signal(&self) -> Option<(usize, SignalAction)> {
+        let mut inner = self.inner.lock();
+        let current = inner.current_task();
+        let pid = inner.tasks[current].pid?;
+        let task = &inner.tasks[current];
+        let in_handler = task.trap_cx_backup.is_some();
+        let deliverable = task.signals - task.signal_mask;
+        let (signum, action) = (1..=MAX_SIG)
+            .filter(|&signum| deliverable.contains(SignalFlags::from_signum(signum).unwrap()))
+            .map(|signum| (signum, inner.processes[pid].signal_actions[signum]))
+            .find(|(_, action)| {
+                !in_handler || action.handler == SIG_DFL || action.handler == SIG_IGN
+            })?;
+        inner.tasks[current].signals -= SignalFlags::from_signum(signum).unwrap();
+        Some((signum, action))
+    }
+
+    /// Redirect current task to the user handler of `signum` when it returns to user mode.
+    fn enter_current_signal_handler(
+        &self,
+        signum: usize,
+        action: &SignalAction,
+        cx: &mut TrapContext,
+    ) {
+        let mut inner = self.inner.lock();
+        let current = inner.current_task();
+        let task = &mut inner.tasks[current];
+        // the handler may use fp, keep the interrupted fp state along with the user context
+        if cx.sstatus.fs() == FS::Dirty {
+            task.fp_cx.save();
+            cx.sstatus.set_fs(FS::Clean);
+        }
+        task.trap_cx_backup = Some(*cx);
+        task.fp_cx_backup = task.fp_cx;
+        task.signal_mask_backup = task.signal_mask;
+        task.signal_mask |= action.mask | SignalFlags::from_signum(signum).unwrap();
+        task.signal_mask -= SignalFlags::unmaskable();
+        cx.sepc = action.handler;
+        cx.x[10] = signum;
+    }
+
+    /// Restore the user context interrupted by a signal handler into `cx` and return its a0,
+    /// or `EINVAL` if current task is not running a signal handler.
+    fn current_sigreturn(&self, cx: &mut TrapContext) -> SysResult {
+        let mut inner = self.inner.lock();
+        let current = inner.current_task();
+        let task = &mut inner.tasks[current];
+        match task.trap_cx_backup.take() {
+            Some(trap_cx) => {
+                task.signal_mask = task.signal_mask_backup;
+                *cx = trap_cx;
+                // the fp registers of any hart may hold the state of the handler
+                task.fp_cx = task.fp_cx_backup;
+                task.fp_hart = None;
+                Ok(trap_cx.x[10] as isize)
+            }
+            None => Err(SysError::EINVAL),
+        }
+    }
+
+    /// Add a new `Ready` task and return its id.
+    fn add_task(&self, mut task: TaskControlBlock) -> usize {
+        let mut inner = self.inner.lock();
+        task.task_status = TaskStatus::Ready;
+        let id = inner.tasks.push(task);
+        inner.push_ready(id);
+        id
+    }
+
+    fn take_current_entry(&self) -> Box<dyn FnOnce() + Send> {
+        let mut inner = self.inner.lock();
+        let current = inner.current_task();
+        inner.tasks[current].entry.take().unwrap()
+    }
+
+    /// Current task trapped into kernel from user mode, save its fp registers if it has dirtied them.
+    fn current_trap_enter(&self, cx: &mut TrapContext) {
+        let mut inner = self.inner.lock();
+        let current = inner.current_task();
+        let task = &mut inner.tasks[current];
+        task.user_time_end(get_time());
+        if cx.sstatus.fs() == FS::Dirty {
+            task.fp_cx.save();
+            cx.sstatus.set_fs(FS::Clean);
+        }
+    }
+
+    /// Current task is returning to user mode, load its fp registers if it uses them
+    /// and they are not on this hart already.
+    ///
+    /// A task never using fp runs with `sstatus.FS` `Off` and pays nothing,
+    /// its first fp instruction traps and turns fp on, see `trap_handler`.
+    fn current_trap_exit(&self, cx: &mut TrapContext) {
+        let mut inner = self.inner.lock();
+        let hart = hart_id();
+        let current = inner.current_task();
+        inner.tasks[current].kernel_time_end(get_time());
+        if cx.sstatus.fs() == FS::Off {
+            return;
+        }
+        // the registers may hold a newer state of another task, or an older one of this task
+        // if it has run on another hart since
+        let loaded = inner.tasks[current].fp_hart == Some(hart);
+        if !loaded || inner.processors[hart].fp_owner != Some(current) {
+            let task = &mut inner.tasks[current];
+            enable_fp();
+            task.fp_cx.restore();
+            task.fp_hart = Some(hart);
+            inner.processors[hart].fp_owner = Some(current);
+            cx.sstatus.set_fs(FS::Clean);
+        }
+    }
+
+    fn count_current_syscall(&self, syscall_id: usize) {
+        let mut inner = self.inner.lock();
+        let current = inner.current_task();
+        if let Some(times) = inner.tasks[current].syscall_times.get_mut(syscall_id) {
+            *times += 1;
+        }
     }
 
-    /// Find next task to run and return app id.
+    /// Fill `info` with statistics of current task, with the kernel time charged up to now.
+    fn current_task_info(&self, info: &mut TaskInfo) {
+        let mut inner = self.inner.lock();
+        let current = inner.current_task();
+        inner.tasks[current].kernel_time_end(get_time());
+        inner.tasks[current].fill_task_info(info);
+    }
+
+    /// Statistics of current task in one line, with the kernel time charged up to now.
+    fn current_usage_summary(&self) -> String {
+        let mut inner = self.inner.lock();
+        let current = inner.current_task();
+        inner.tasks[current].kernel_time_end(get_time());
+        inner.tasks[current].usage_summary()
+    }
+
+    /// Charge the running time to current task and check whether it should give up the cpu.
+    ///
+    /// A real-time task is preempted when its budget is used up or an earlier deadline is ready,
+    /// a best-effort task whenever any real-time task is ready.
+    fn current_should_preempt(&self) -> bool {
+        let mut inner = self.inner.lock();
+        let inner = &mut *inner;
+        let now = get_time();
+        let current = inner.current_task();
+        inner.tasks[current].update_runtime(now);
+        inner.rt_scheduler.replenish(now, &mut inner.tasks);
+        let earliest_deadline = inner.rt_scheduler.earliest_deadline();
+        match inner.tasks[current].dl.as_ref() {
+            Some(dl) => dl.budget == 0 || earliest_deadline.map_or(false, |d| d < dl.abs_deadline),
+            None => {
+                earliest_deadline.is_some() || inner.scheduler.should_preempt(&inner.tasks[current])
+            }
+        }
+    }
+
+    /// Whether current task has used up its cpu time or wall-clock limit.
+    fn current_time_limit_exceeded(&self) -> bool {
+        let inner = self.inner.lock();
+        inner.tasks[inner.current_task()].time_limit_exceeded(get_time())
+    }
+
+    /// Set a resource limit of current task, or of its process for `RLIMIT_AS`, see `RLIMIT_*`,
+    /// `EINVAL` if `resource` is unknown or the limit overflows.
+    fn set_current_rlimit(&self, resource: usize, limit: usize) -> SysResult {
+        let mut inner = self.inner.lock();
+        let current = inner.current_task();
+        let ms_to_time = |ms: usize| match ms {
+            RLIM_INFINITY => Some(usize::MAX),
+            ms => ms.checked_mul(1000).and_then(us_to_time),
+        };
+        let task = &mut inner.tasks[current];
+        match resource {
+            RLIMIT_CPU => task.cpu_limit = ms_to_time(limit).ok_or(SysError::EINVAL)?,
+            RLIMIT_AS => {
+                let pid = task.pid.ok_or(SysError::EINVAL)?;
+                inner.processes[pid].pages.set_limit(limit);
+            }
+            RLIMIT_WALL => task.wall_limit = ms_to_time(limit).ok_or(SysError::EINVAL)?,
+            _ => return Err(SysError::EINVAL),
+        }
+        Ok(0)
+    }
+
+    /// Whether user addresses `[start, end)` are all in one memory region of the process of
+    /// current task: its app slot or the user stack of one of its threads.
+    fn current_owns_user_range(&self, start: usize, end: usize) -> bool {
+        let inner = self.inner.lock();
+        let pid = match inner.tasks[inner.current_task()].pid {
+            Some(pid) => pid,
+            None => return false,
+        };
+        let within = |region: &Range<usize>| region.start <= start && end <= region.end;
+        get_app_regions(pid).iter().any(within)
+            || inner.processes[pid]
+                .thread_tasks()
+                .filter_map(|id| inner.tasks.get(id)?.user_stack.as_ref())
+                .any(|stack| within(&stack.range()))
+    }
+
+    /// Pages of the process of the task on current hart, `None` in the idle loop.
+    fn current_page_counter(&self) -> Option<Arc<PageCounter>> {
+        let inner = self.inner.lock();
+        let current = inner.processors[hart_id()].current?;
+        let pid = inner.tasks[current].pid?;
+        Some(inner.processes[pid].pages.clone())
+    }
+
+    /// Make current task periodic real-time with parameters in microseconds,
+    /// or best-effort again if all of them are 0.
     ///
-    /// In this case, we only return the first `Ready` task in task list.
-    fn find_next_task(&self) -> Option<usize> {
-        let inner = self.inner.exclusive_access();
-        let current = inner.current_task;
-        (current + 1..current + self.num_app + 1)
-            .map(|id| id % self.num_app)
-            .find(|id| inner.tasks[*id].task_status == TaskStatus::Ready)
+    /// Return `EINVAL` if parameters are invalid or overflow, or `EBUSY` if admission control fails.
+    fn set_current_deadline(&self, runtime: usize, deadline: usize, period: usize) -> SysResult {
+        let mut inner = self.inner.lock();
+        let current = inner.current_task();
+        let now = get_time();
+        inner.tasks[current].update_runtime(now);
+        if runtime == 0 && deadline == 0 && period == 0 {
+            if let Some(dl) = inner.tasks[current].dl.take() {
+                inner.rt_scheduler.release(&dl);
+            }
+            return Ok(0);
+        }
+        if runtime == 0 || runtime > deadline || deadline > period {
+            return Err(SysError::EINVAL);
+        }
+        let dl = match (
+            us_to_time(runtime),
+            us_to_time(deadline),
+            us_to_time(period),
+        ) {
+            (Some(runtime), Some(deadline), Some(period)) => {
+                DeadlineEntity::new(runtime, deadline, period, now)
+            }
+            _ => return Err(SysError::EINVAL),
+        };
+        let old = inner.tasks[current].dl;
+        if !inner.rt_scheduler.admit(old.as_ref(), &dl) {
+            return Err(SysError::EBUSY);
+        }
+        inner.tasks[current].dl = Some(dl);
+        Ok(0)
     }
 
-    /// Switch current `Running` task to the task we have found,
-    /// or there is no `Ready` task and we can exit with all applications completed
+    /// Set nice value of current task, `EINVAL` if `nice` is out of range.
+    fn set_current_nice(&self, nice: isize) -> SysResult {
+        if !(NICE_MIN..=NICE_MAX).contains(&nice) {
+            return Err(SysError::EINVAL);
+        }
+        let mut inner = self.inner.lock();
+        let current = inner.current_task();
+        // time already run is charged with the old weight
+        inner.tasks[current].update_runtime(get_time());
+        inner.tasks[current].nice = nice;
+        Ok(nice)
+    }
+
+    /// Switch from current task, which is no longer `Running`, back to the idle loop of current hart.
     fn run_next_task(&self) {
-        if let Some(next) = self.find_next_task() {
-            let mut inner = self.inner.exclusive_access();
-            let current = inner.current_task;
-            inner.tasks[next].task_status = TaskStatus::Running;
-            inner.current_task = next;
-            println!(
-                "current task: {}, next task: {}",
-                inner.tasks[current], inner.tasks[next]
-            );
-            let current_task_cx_ptr = &mut inner.tasks[current].task_cx as *mut TaskContext;
-            let next_task_cx_ptr = &inner.tasks[next].task_cx as *const TaskContext;
-            drop(inner);
-            // before this, we should drop local variables that must be dropped manually
-            unsafe {
-                __switch(current_task_cx_ptr, next_task_cx_ptr);
-            }
-            // go back to user mode
-        } else {
-            panic!("All applications completed!");
+        let mut inner = self.inner.lock();
+        let current = inner.current_task();
+        let current_task_cx_ptr = &mut inner.tasks[current].task_cx as *mut TaskContext;
+        let idle_task_cx_ptr = &inner.processors[hart_id()].idle_task_cx as *const TaskContext;
+        drop(inner);
+        // before this, we should drop local variables that must be dropped manually
+        unsafe {
+            __switch(current_task_cx_ptr, idle_task_cx_ptr);
         }
+        // go back to user mode
+    }
+}
+
+/// Wait in low power state until an interrupt is pending.
+///
+/// Interrupts are disabled in kernel, so a pending timer is only re-armed here,
+/// and a pending ipi, which only means some task may be `Ready` now, is cleared.
+fn wait_for_interrupt() {
+    crate::riscv::wfi();
+    if sip::read().stimer() {
+        check_timer();
+        set_next_trigger();
+    }
+    if sip::read().ssoft() {
+        crate::riscv::clear_ssoft();
+    }
+    if sip::read().sext() {
+        handle_external_interrupt();
     }
 }
 
-pub fn run_first_task() {
-    TASK_MANAGER.run_first_task();
+pub fn run_tasks() -> ! {
+    TASK_MANAGER.run_tasks()
 }
 
 fn run_next_task() {
@@ -133,8 +903,8 @@ fn mark_current_suspended() {
     TASK_MANAGER.mark_current_suspended();
 }
 
-fn mark_current_exited() {
-    TASK_MANAGER.mark_current_exited();
+fn mark_current_exited(exit_code: i32) {
+    TASK_MANAGER.mark_current_exited(exit_code);
 }
 
 pub fn suspend_current_and_run_next() {
@@ -142,7 +912,250 @@ pub fn suspend_current_and_run_next() {
     run_next_task();
 }
 
-pub fn exit_current_and_run_next() {
-    mark_current_exited();
+/// Block current task until `wakeup_task` is called on it.
+pub fn block_current_and_run_next() {
+    TASK_MANAGER.mark_current_blocked(false);
+    run_next_task();
+}
+
+/// Block current task like `block_current_and_run_next`, calling `release` once the task is
+/// `Blocked`, so that a wakeup done under a lock released there is never lost.
+///
+/// If `interruptible`, a signal to be handled wakes the task up as well, and false is returned.
+pub fn block_current_and_run_next_with<F: FnOnce()>(interruptible: bool, release: F) -> bool {
+    TASK_MANAGER.mark_current_blocked(interruptible);
+    release();
+    run_next_task();
+    !TASK_MANAGER.take_current_interrupted()
+}
+
+/// Block current task until `expire` time, return false if a signal interrupts the sleep.
+pub fn sleep_current_until(expire: usize) -> bool {
+    let current = TASK_MANAGER.mark_current_blocked(true);
+    add_timer(expire, current);
+    run_next_task();
+    if TASK_MANAGER.take_current_interrupted() {
+        remove_timer(current);
+        return false;
+    }
+    true
+}
+
+pub fn wakeup_task(id: usize) -> bool {
+    TASK_MANAGER.wakeup_task(id)
+}
+
+pub fn exit_current_and_run_next(exit_code: i32) {
+    clear_current_child_tid();
+    mark_current_exited(exit_code);
+    run_next_task();
+}
+
+/// Exit all threads of current process, like `exit_group` of Linux.
+pub fn exit_current_group_and_run_next(exit_code: i32) {
+    clear_current_child_tid();
+    TASK_MANAGER.mark_current_group_exited(exit_code);
     run_next_task();
 }
+
+pub fn set_current_clear_child_tid(addr: usize) -> usize {
+    TASK_MANAGER.set_current_clear_child_tid(addr)
+}
+
+/// Clear the u32 at `clear_child_tid` of current task and wake up a waiter on it,
+/// which is how a thread library joins an exiting thread.
+fn clear_current_child_tid() {
+    let addr = TASK_MANAGER.take_current_clear_child_tid();
+    if addr == 0 || addr % 4 != 0 {
+        return;
+    }
+    if let Some(pa) = translate_user_addr(addr, true) {
+        unsafe { *(pa as *mut u32) = 0 };
+        futex_wake(pa, 1);
+    }
+}
+
+/// Called on timer interrupt, whether current task should be preempted.
+pub fn current_should_preempt() -> bool {
+    TASK_MANAGER.current_should_preempt()
+}
+
+/// Called on timer interrupt, terminate current task with `EXIT_RLIMIT`
+/// if it has used up its cpu time or wall-clock limit.
+pub fn check_current_time_limit() {
+    if TASK_MANAGER.current_time_limit_exceeded() {
+        println!(
+            "[kernel] Task {} exceeded its time limit, kernel killed it.",
+            current_task_id()
+        );
+        exit_current_and_run_next(EXIT_RLIMIT);
+    }
+}
+
+pub fn set_current_rlimit(resource: usize, limit: usize) -> SysResult {
+    TASK_MANAGER.set_current_rlimit(resource, limit)
+}
+
+/// Whether user addresses `[start, end)` belong to the process of current task,
+/// which is all that keeps it from passing the kernel other memory while paging is off.
+pub fn current_owns_user_range(start: usize, end: usize) -> bool {
+    TASK_MANAGER.current_owns_user_range(start, end)
+}
+
+/// Pages of the process of current task, which page frames it allocates are charged to.
+pub fn current_page_counter() -> Option<Arc<PageCounter>> {
+    TASK_MANAGER.current_page_counter()
+}
+
+pub fn set_current_nice(nice: isize) -> SysResult {
+    TASK_MANAGER.set_current_nice(nice)
+}
+
+pub fn set_current_deadline(runtime: usize, deadline: usize, period: usize) -> SysResult {
+    TASK_MANAGER.set_current_deadline(runtime, deadline, period)
+}
+
+/// Give up the cpu, a real-time task also gives up the rest of its budget in this period.
+pub fn yield_current_and_run_next() {
+    TASK_MANAGER.mark_current_job_done();
+    suspend_current_and_run_next();
+}
+
+pub fn current_trap_enter(cx: &mut TrapContext) {
+    TASK_MANAGER.current_trap_enter(cx);
+}
+
+pub fn current_trap_exit(cx: &mut TrapContext) {
+    TASK_MANAGER.current_trap_exit(cx);
+}
+
+pub fn count_current_syscall(syscall_id: usize) {
+    TASK_MANAGER.count_current_syscall(syscall_id);
+}
+
+pub fn current_task_info(info: &mut TaskInfo) {
+    TASK_MANAGER.current_task_info(info);
+}
+
+pub fn current_usage_summary() -> String {
+    TASK_MANAGER.current_usage_summary()
+}
+
+/// Spawn a kernel thread running `f` in supervisor mode, return its task id.
+pub fn kthread_spawn<F>(f: F) -> usize
+where
+    F: FnOnce() + Send + 'static,
+{
+    TASK_MANAGER.add_task(TaskControlBlock::new_kthread(Box::new(f)))
+}
+
+fn take_current_entry() -> Box<dyn FnOnce() + Send> {
+    TASK_MANAGER.take_current_entry()
+}
+
+pub fn create_thread(entry: usize, arg: usize) -> SysResult {
+    TASK_MANAGER.create_thread(entry, arg)
+}
+
+/// Run `f` on the process of current task, return `None` for kernel threads.
+///
+/// `f` runs with the task manager locked, blocking calls must be made after it returns.
+pub fn with_current_process<R>(f: impl FnOnce(&mut ProcessControlBlock) -> R) -> Option<R> {
+    TASK_MANAGER.with_current_process(f)
+}
+
+/// Id of the task running on current hart.
+pub fn current_task_id() -> usize {
+    TASK_MANAGER.current_task_id()
+}
+
+/// Id of the task running on current hart, `None` in the idle loop.
+pub fn try_current_task_id() -> Option<usize> {
+    TASK_MANAGER.try_current_task_id()
+}
+
+pub fn current_tid() -> usize {
+    TASK_MANAGER.current_tid()
+}
+
+lazy_static! {
+    /// threads blocked in `waittid`, woken up whenever an exited thread has switched out
+    static ref EXIT_WAIT_QUEUE: WaitQueue = WaitQueue::new();
+    /// held while checking for and announcing thread exits, so that no waiter misses one
+    static ref EXIT_LOCK: SpinLock<()> = SpinLock::new(());
+}
+
+/// Wake up all `waittid` waiters to check again, called once an exited task is off its hart.
+fn announce_task_exit() {
+    let _exits = EXIT_LOCK.lock();
+    EXIT_WAIT_QUEUE.wake_all();
+}
+
+/// Block until thread `tid` of current process exits, then reclaim it and return its exit code,
+/// `EINTR` if a signal interrupts the wait.
+pub fn waittid(tid: usize) -> SysResult {
+    loop {
+        let exits = EXIT_LOCK.lock();
+        match TASK_MANAGER.waittid(tid) {
+            Err(SysError::EAGAIN) => {
+                if !EXIT_WAIT_QUEUE.sleep_on_and_release(|| drop(exits)) {
+                    return Err(SysError::EINTR);
+                }
+            }
+            result => return result,
+        }
+    }
+}
+
+pub fn set_trace_mask(pid: usize, mask: usize) -> SysResult {
+    TASK_MANAGER.set_trace_mask(pid, mask)
+}
+
+/// Syscall classes traced for current task, 0 for kernel threads.
+pub fn current_trace_mask() -> usize {
+    with_current_process(|process| process.trace_mask).unwrap_or(0)
+}
+
+pub fn kill(pid: usize, signum: usize) -> SysResult {
+    TASK_MANAGER.kill(pid, signum)
+}
+
+/// Raise `signum` on current task for a fault it caused, it is delivered even if blocked or ignored.
+pub fn force_current_signal(signum: usize) {
+    TASK_MANAGER.force_current_signal(signum);
+}
+
+pub fn set_current_sigaction(signum: usize, action: Option<SignalAction>) -> Option<SignalAction> {
+    TASK_MANAGER.set_current_sigaction(signum, action)
+}
+
+pub fn set_current_sigmask(mask: u32) -> u32 {
+    TASK_MANAGER
+        .set_current_sigmask(SignalFlags::from_bits_truncate(mask))
+        .bits()
+}
+
+/// Restore the user context interrupted by a signal handler into `cx`, the live trap context.
+pub fn current_sigreturn(cx: &mut TrapContext) -> SysResult {
+    TASK_MANAGER.current_sigreturn(cx)
+}
+
+/// Deliver pending signals of current task right before it returns to user mode.
+///
+/// The default action of most signals terminates the task with `signal_exit_code`.
+pub fn handle_current_signals(cx: &mut TrapContext) {
+    while let Some((signum, action)) = TASK_MANAGER.take_current_signal() {
+        match action.handler {
+            SIG_IGN => {}
+            SIG_DFL if default_ignored(signum) => {}
+            SIG_DFL => {
+                println!("[kernel] Application killed by signal {}", signum);
+                exit_current_and_run_next(signal_exit_code(signum));
+            }
+            _ => {
+                TASK_MANAGER.enter_current_signal_handler(signum, &action, cx);
+                break;
+            }
+        }
+    }
+}
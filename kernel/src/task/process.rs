@@ -0,0 +1,242 @@
+use super::deadlock::DeadlockDetector;
+use super::signal::{SignalAction, MAX_SIG};
+use crate::config::PAGE_SIZE;
+use crate::fs::{File, Stderr, Stdin, Stdout};
+use crate::sync::{Condvar, MutexBlocking, Semaphore};
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// Tid of the main thread of an app, tids are positive as on Linux, where libc uses them
+/// as lock owners.
+pub const MAIN_TID: usize = 1;
+
+/// Resources shared by all threads of an app, the pid of an app is its app id.
+pub struct ProcessControlBlock {
+    /// task id of each thread indexed by tid, `None` after the thread has been waited
+    /// and in the unused slot 0
+    pub threads: Vec<Option<usize>>,
+    /// open files indexed by fd, `None` after the fd is closed
+    pub fd_table: Vec<Option<Arc<dyn File>>>,
+    /// action of each signal number
+    pub signal_actions: [SignalAction; MAX_SIG + 1],
+    /// mutexes created by `sys_mutex_create`, indexed by mutex id
+    pub mutex_list: Vec<Option<Arc<MutexBlocking>>>,
+    /// semaphores created by `sys_semaphore_create`, indexed by semaphore id
+    pub semaphore_list: Vec<Option<Arc<Semaphore>>>,
+    /// condition variables created by `sys_condvar_create`, indexed by condvar id
+    pub condvar_list: Vec<Option<Arc<Condvar>>>,
+    /// whether to refuse locks and downs which may deadlock, set by `sys_enable_deadlock_detect`
+    pub deadlock_detect: bool,
+    /// usage of mutexes by threads, kept even if detection is off
+    pub mutex_detector: DeadlockDetector,
+    /// usage of semaphores by threads, kept even if detection is off
+    pub semaphore_detector: DeadlockDetector,
+    /// syscall classes traced for all threads, see `sys_trace`
+    pub trace_mask: usize,
+    /// start of the heap, right after the image
+    pub heap_bottom: usize,
+    /// program break, the end of the heap, moved by `sys_brk`
+    pub brk: usize,
+    /// lowest anonymous mapping, they are allocated downwards from the end of free memory
+    pub mmap_bottom: usize,
+    /// pages of heap, anonymous mappings and page frames, limited by `RLIMIT_AS`
+    pub pages: Arc<PageCounter>,
+}
+
+impl ProcessControlBlock {
+    /// Create a process whose main thread (tid `MAIN_TID`) is task `main_task`.
+    pub fn new(main_task: usize) -> Self {
+        Self {
+            threads: vec![None, Some(main_task)],
+            fd_table: vec![
+                Some(Arc::new(Stdin)),
+                Some(Arc::new(Stdout)),
+                Some(Arc::new(Stderr)),
+            ],
+            signal_actions: [SignalAction::default(); MAX_SIG + 1],
+            mutex_list: Vec::new(),
+            semaphore_list: Vec::new(),
+            condvar_list: Vec::new(),
+            deadlock_detect: false,
+            mutex_detector: DeadlockDetector::new(),
+            semaphore_detector: DeadlockDetector::new(),
+            trace_mask: 0,
+            heap_bottom: 0,
+            brk: 0,
+            mmap_bottom: 0,
+            pages: Arc::new(PageCounter::new()),
+        }
+    }
+
+    /// Heap and anonymous mappings share the free memory `[start, end)` of the process,
+    /// growing towards each other.
+    pub fn init_free_memory(&mut self, start: usize, end: usize) {
+        self.heap_bottom = start;
+        self.brk = start;
+        self.mmap_bottom = end;
+    }
+
+    /// Move the program break to `addr` and return the new break, or the old one if `addr`
+    /// is out of the free memory, e.g. 0 to query it.
+    pub fn set_brk(&mut self, addr: usize) -> usize {
+        if addr < self.heap_bottom || addr > self.mmap_bottom {
+            return self.brk;
+        }
+        let old_pages = page_count(self.brk - self.heap_bottom);
+        let new_pages = page_count(addr - self.heap_bottom);
+        if new_pages > old_pages && !self.pages.charge(new_pages - old_pages) {
+            return self.brk;
+        }
+        self.pages.uncharge(old_pages.saturating_sub(new_pages));
+        if addr > self.brk {
+            zero_memory(self.brk, addr);
+        }
+        self.brk = addr;
+        addr
+    }
+
+    /// Take `len` bytes of zeroed, page aligned anonymous memory,
+    /// `None` if it would run into the heap or exceed `RLIMIT_AS`.
+    pub fn alloc_anonymous(&mut self, len: usize) -> Option<usize> {
+        let start = self.mmap_bottom.checked_sub(len)? & !(PAGE_SIZE - 1);
+        if start < self.brk || !self.pages.charge(page_count(self.mmap_bottom - start)) {
+            return None;
+        }
+        zero_memory(start, self.mmap_bottom);
+        self.mmap_bottom = start;
+        Some(start)
+    }
+
+    /// Tid of the next thread added, tids of reclaimed threads are not reused.
+    pub fn next_tid(&self) -> usize {
+        self.threads.len()
+    }
+
+    /// Register task `task_id` as a new thread with tid `next_tid`.
+    pub fn add_thread(&mut self, task_id: usize) {
+        self.threads.push(Some(task_id));
+    }
+
+    /// Tid of the thread run by task `task_id`.
+    pub fn tid_of(&self, task_id: usize) -> Option<usize> {
+        self.threads.iter().position(|task| *task == Some(task_id))
+    }
+
+    /// Forget the synchronization objects thread `tid` holds or waits for, it has exited.
+    pub fn thread_exited(&mut self, tid: usize) {
+        self.mutex_detector.remove_thread(tid);
+        self.semaphore_detector.remove_thread(tid);
+    }
+
+    /// Task ids of all threads not waited yet.
+    pub fn thread_tasks(&self) -> impl Iterator<Item = usize> + '_ {
+        self.threads.iter().filter_map(|task_id| *task_id)
+    }
+
+    /// Open `file` at the lowest free fd and return the fd.
+    pub fn add_file(&mut self, file: Arc<dyn File>) -> usize {
+        insert(&mut self.fd_table, file)
+    }
+
+    pub fn get_file(&self, fd: usize) -> Option<Arc<dyn File>> {
+        self.fd_table.get(fd).cloned().flatten()
+    }
+
+    /// Close `fd` and return its file, `None` if it is not open.
+    pub fn close_file(&mut self, fd: usize) -> Option<Arc<dyn File>> {
+        self.fd_table.get_mut(fd)?.take()
+    }
+
+    pub fn add_mutex(&mut self, mutex: MutexBlocking) -> usize {
+        let id = insert(&mut self.mutex_list, Arc::new(mutex));
+        self.mutex_detector.add_resource(id, 1);
+        id
+    }
+
+    pub fn get_mutex(&self, id: usize) -> Option<Arc<MutexBlocking>> {
+        self.mutex_list.get(id).cloned().flatten()
+    }
+
+    pub fn add_semaphore(&mut self, semaphore: Semaphore, res_count: usize) -> usize {
+        let id = insert(&mut self.semaphore_list, Arc::new(semaphore));
+        self.semaphore_detector.add_resource(id, res_count);
+        id
+    }
+
+    pub fn get_semaphore(&self, id: usize) -> Option<Arc<Semaphore>> {
+        self.semaphore_list.get(id).cloned().flatten()
+    }
+
+    pub fn add_condvar(&mut self, condvar: Condvar) -> usize {
+        insert(&mut self.condvar_list, Arc::new(condvar))
+    }
+
+    pub fn get_condvar(&self, id: usize) -> Option<Arc<Condvar>> {
+        self.condvar_list.get(id).cloned().flatten()
+    }
+}
+
+/// Pages charged to a process against its `RLIMIT_AS`.
+///
+/// Atomic rather than under the task manager lock, as page frames are freed wherever
+/// their `FrameTracker` is dropped, including with that lock held.
+pub struct PageCounter {
+    pages: AtomicUsize,
+    limit: AtomicUsize,
+}
+
+impl PageCounter {
+    pub fn new() -> Self {
+        Self {
+            pages: AtomicUsize::new(0),
+            limit: AtomicUsize::new(usize::MAX),
+        }
+    }
+
+    /// Pages already charged are kept even if they exceed the new limit.
+    pub fn set_limit(&self, limit: usize) {
+        self.limit.store(limit, Ordering::Relaxed);
+    }
+
+    /// Charge `count` pages, return false if it would exceed the limit.
+    pub fn charge(&self, count: usize) -> bool {
+        let limit = self.limit.load(Ordering::Relaxed);
+        self.pages
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |pages| {
+                pages.checked_add(count).filter(|&pages| pages <= limit)
+            })
+            .is_ok()
+    }
+
+    /// Give back `count` pages charged by `charge`.
+    pub fn uncharge(&self, count: usize) {
+        let pages = self.pages.fetch_sub(count, Ordering::Relaxed);
+        debug_assert!(pages >= count, "Uncharge {} of {} pages", count, pages);
+    }
+}
+
+/// Pages needed for `len` bytes.
+fn page_count(len: usize) -> usize {
+    (len + PAGE_SIZE - 1) / PAGE_SIZE
+}
+
+/// Put `item` into the first free slot of `list`, return its index.
+fn insert<T>(list: &mut Vec<Option<T>>, item: T) -> usize {
+    match list.iter().position(|slot| slot.is_none()) {
+        Some(id) => {
+            list[id] = Some(item);
+            id
+        }
+        None => {
+            list.push(Some(item));
+            list.len() - 1
+        }
+    }
+}
+
+/// Memory of user processes is identical to physical memory, as paging is not on.
+fn zero_memory(start: usize, end: usize) {
+    unsafe { core::ptr::write_bytes(start as *mut u8, 0, end - start) }
+}
@@ -0,0 +1,24 @@
+use super::TaskContext;
+
+/// Scheduling state of a hart, indexed by hart id in `TaskManagerInner::processors`.
+pub struct Processor {
+    /// whether the hart has entered its idle loop
+    pub online: bool,
+    /// task running on the hart, `None` while the hart is in its idle loop
+    pub current: Option<usize>,
+    /// context of the idle loop in `run_tasks`, every task running on the hart switches back here
+    pub idle_task_cx: TaskContext,
+    /// task whose fp state is in the fp registers of the hart
+    pub fp_owner: Option<usize>,
+}
+
+impl Processor {
+    pub fn new() -> Self {
+        Self {
+            online: false,
+            current: None,
+            idle_task_cx: TaskContext::zero_init(),
+            fp_owner: None,
+        }
+    }
+}
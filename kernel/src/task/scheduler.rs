@@ -0,0 +1,15 @@
+use super::task::TaskControlBlock;
+
+/// Policy deciding which `Ready` task gets the cpu next.
+pub trait Scheduler {
+    fn new() -> Self;
+
+    /// Put a `Ready` task into the run queue.
+    fn push(&mut self, id: usize, task: &mut TaskControlBlock);
+
+    /// Take the task that should run next out of the run queue.
+    fn pop(&mut self) -> Option<usize>;
+
+    /// Whether the `Running` task should give up the cpu at a timer tick.
+    fn should_preempt(&self, current: &TaskControlBlock) -> bool;
+}
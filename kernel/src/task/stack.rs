@@ -0,0 +1,50 @@
+use crate::config::PAGE_SIZE;
+use crate::trap::TrapContext;
+use alloc::alloc::{alloc_zeroed, dealloc};
+use core::alloc::Layout;
+use core::ops::Range;
+
+/// Stack allocated on kernel heap and freed on drop.
+///
+/// Used as kernel stack of threads created at runtime, and as user stack of user threads,
+/// which user mode can access since the kernel does not enable paging for apps yet.
+pub struct HeapStack {
+    bottom: usize,
+    size: usize,
+}
+
+impl HeapStack {
+    fn layout(size: usize) -> Layout {
+        Layout::from_size_align(size, PAGE_SIZE).unwrap()
+    }
+
+    pub fn new(size: usize) -> Self {
+        let bottom = unsafe { alloc_zeroed(Self::layout(size)) } as usize;
+        assert_ne!(bottom, 0, "Out of memory for stack!");
+        Self { bottom, size }
+    }
+
+    pub fn get_top(&self) -> usize {
+        self.bottom + self.size
+    }
+
+    pub fn range(&self) -> Range<usize> {
+        self.bottom..self.get_top()
+    }
+
+    /// Save `TrapContext` on the top of the stack, return its address.
+    pub fn push_context(&self, trap_cx: TrapContext) -> usize {
+        let trap_cx_ptr =
+            (self.get_top() - core::mem::size_of::<TrapContext>()) as *mut TrapContext;
+        unsafe {
+            *trap_cx_ptr = trap_cx;
+        }
+        trap_cx_ptr as usize
+    }
+}
+
+impl Drop for HeapStack {
+    fn drop(&mut self) {
+        unsafe { dealloc(self.bottom as *mut u8, Self::layout(self.size)) }
+    }
+}
@@ -1,10 +1,229 @@
+use super::cfs::{nice_to_weight, NICE_0_WEIGHT};
+use super::edf::DeadlineEntity;
+use super::fp::FpContext;
+use super::signal::SignalFlags;
+use super::stack::HeapStack;
 use super::TaskContext;
+use crate::config::{KERNEL_STACK_SIZE, MAX_SYSCALL_NUM, USER_STACK_SIZE};
+use crate::timer::time_to_us;
+use crate::trap::TrapContext;
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec;
+use core::fmt::Write;
 use core::fmt::{Display, Formatter};
 
-#[derive(Copy, Clone)]
 pub struct TaskControlBlock {
     pub task_status: TaskStatus,
     pub task_cx: TaskContext,
+    /// whether the task is a kernel thread, which never returns to user mode
+    pub kthread: bool,
+    /// process the task belongs to as a thread, `None` for kernel threads
+    pub pid: Option<usize>,
+    /// thread id inside the process, the main thread of an app is `MAIN_TID`, 0 for kernel threads
+    pub tid: usize,
+    /// kernel stack allocated on heap, main threads of apps use the static ones in `loader`
+    pub kernel_stack: Option<HeapStack>,
+    /// user stack allocated on heap for threads created by `sys_thread_create`
+    pub user_stack: Option<HeapStack>,
+    /// pending signals
+    pub signals: SignalFlags,
+    /// blocked signals
+    pub signal_mask: SignalFlags,
+    /// `signal_mask` to restore on `sys_sigreturn`
+    pub signal_mask_backup: SignalFlags,
+    /// whether a signal to be handled wakes the task up while it is `Blocked`
+    pub interruptible: bool,
+    /// woken up by a signal rather than `wakeup_task`, the blocking call fails with `EINTR`
+    pub interrupted: bool,
+    /// user context interrupted by a signal handler, restored on `sys_sigreturn`
+    pub trap_cx_backup: Option<TrapContext>,
+    /// fp registers, valid when `sstatus.FS` of the user context is not `Off`
+    pub fp_cx: FpContext,
+    /// hart whose fp registers were last loaded with `fp_cx`
+    pub fp_hart: Option<usize>,
+    /// `fp_cx` of the user context in `trap_cx_backup`, restored on `sys_sigreturn`
+    pub fp_cx_backup: FpContext,
+    /// function a kernel thread runs, taken by `kthread_trampoline` when first scheduled
+    pub entry: Option<Box<dyn FnOnce() + Send>>,
+    /// nice value in [-20, 19], the lower the more cpu share
+    pub nice: isize,
+    /// run time weighted by nice, in timer cycles
+    pub vruntime: usize,
+    /// time when the task was switched in or last charged
+    pub sched_time: usize,
+    /// real-time parameters, `None` for best-effort tasks
+    pub dl: Option<DeadlineEntity>,
+    /// exit code submitted by `sys_exit`, or set by kernel when it killed the task
+    pub exit_code: i32,
+    /// time spent in user mode, in timer cycles
+    pub user_time: usize,
+    /// time spent in kernel mode, in timer cycles
+    pub kernel_time: usize,
+    /// time when the task last switched in or crossed the user/kernel boundary
+    pub acct_time: usize,
+    /// how many times the task has been switched in
+    pub switch_count: usize,
+    /// time when the task was scheduled for the first time
+    pub first_run_time: Option<usize>,
+    /// invocation count of each syscall id, boxed to keep the control block small
+    pub syscall_times: Box<[u32]>,
+    /// cpu time limit in timer cycles, `usize::MAX` if unlimited
+    pub cpu_limit: usize,
+    /// wall-clock limit since the first run in timer cycles, `usize::MAX` if unlimited
+    pub wall_limit: usize,
+    /// user address of a u32 cleared and futex-woken when the task exits, 0 if none,
+    /// set by `sys_set_tid_address`
+    pub clear_child_tid: usize,
+}
+
+impl TaskControlBlock {
+    pub fn new(task_cx: TaskContext) -> Self {
+        Self {
+            task_status: TaskStatus::UnInit,
+            task_cx,
+            kthread: false,
+            pid: None,
+            tid: 0,
+            kernel_stack: None,
+            user_stack: None,
+            signals: SignalFlags::empty(),
+            signal_mask: SignalFlags::empty(),
+            signal_mask_backup: SignalFlags::empty(),
+            interruptible: false,
+            interrupted: false,
+            trap_cx_backup: None,
+            fp_cx: FpContext::zero_init(),
+            fp_hart: None,
+            fp_cx_backup: FpContext::zero_init(),
+            entry: None,
+            nice: 0,
+            vruntime: 0,
+            sched_time: 0,
+            dl: None,
+            exit_code: 0,
+            user_time: 0,
+            kernel_time: 0,
+            acct_time: 0,
+            switch_count: 0,
+            first_run_time: None,
+            syscall_times: vec![0; MAX_SYSCALL_NUM].into_boxed_slice(),
+            cpu_limit: usize::MAX,
+            wall_limit: usize::MAX,
+            clear_child_tid: 0,
+        }
+    }
+
+    /// Create a kernel thread running `entry` on its own kernel stack.
+    pub fn new_kthread(entry: Box<dyn FnOnce() + Send>) -> Self {
+        let kernel_stack = HeapStack::new(KERNEL_STACK_SIZE);
+        let mut task = Self::new(TaskContext::goto_kthread(kernel_stack.get_top()));
+        task.kthread = true;
+        task.kernel_stack = Some(kernel_stack);
+        task.entry = Some(entry);
+        task
+    }
+
+    /// Create thread `tid` of process `pid`, which starts at `entry` with `arg` in a0.
+    pub fn new_user_thread(pid: usize, tid: usize, entry: usize, arg: usize) -> Self {
+        let kernel_stack = HeapStack::new(KERNEL_STACK_SIZE);
+        let user_stack = HeapStack::new(USER_STACK_SIZE);
+        let mut trap_cx = TrapContext::app_init_context(entry, user_stack.get_top());
+        trap_cx.x[10] = arg;
+        let trap_cx_ptr = kernel_stack.push_context(trap_cx);
+        let mut task = Self::new(TaskContext::goto_restore(trap_cx_ptr));
+        task.pid = Some(pid);
+        task.tid = tid;
+        task.kernel_stack = Some(kernel_stack);
+        task.user_stack = Some(user_stack);
+        task
+    }
+
+    /// Free stacks of an exited task, must not be called on the stack being used.
+    pub fn release_stacks(&mut self) {
+        self.kernel_stack = None;
+        self.user_stack = None;
+    }
+
+    /// Task gets the cpu at `now`, in kernel mode until `current_trap_exit`.
+    pub fn switch_in(&mut self, now: usize) {
+        self.task_status = TaskStatus::Running;
+        self.sched_time = now;
+        self.acct_time = now;
+        self.switch_count += 1;
+        self.first_run_time.get_or_insert(now);
+    }
+
+    /// Charge the time since `acct_time` as user time, called when trapped into kernel.
+    pub fn user_time_end(&mut self, now: usize) {
+        self.user_time += now - self.acct_time;
+        self.acct_time = now;
+    }
+
+    /// Charge the time since `acct_time` as kernel time,
+    /// called when returning to user mode or giving up the cpu.
+    pub fn kernel_time_end(&mut self, now: usize) {
+        self.kernel_time += now - self.acct_time;
+        self.acct_time = now;
+    }
+
+    /// Fill `info` in place, it is too large to be returned on a kernel stack.
+    pub fn fill_task_info(&self, info: &mut TaskInfo) {
+        info.user_time = time_to_us(self.user_time);
+        info.kernel_time = time_to_us(self.kernel_time);
+        info.switch_count = self.switch_count;
+        info.first_run_time = self.first_run_time.map_or(0, time_to_us);
+        info.syscall_times.copy_from_slice(&self.syscall_times);
+    }
+
+    /// Statistics of the task in one line, syscalls which were never called are left out.
+    pub fn usage_summary(&self) -> String {
+        let mut summary = String::new();
+        write!(
+            summary,
+            "user time {}us, kernel time {}us, {} switches, first run at {}us, syscall times:",
+            time_to_us(self.user_time),
+            time_to_us(self.kernel_time),
+            self.switch_count,
+            self.first_run_time.map_or(0, time_to_us)
+        )
+        .unwrap();
+        for (id, times) in self.syscall_times.iter().enumerate() {
+            if *times > 0 {
+                write!(summary, " {}={}", id, times).unwrap();
+            }
+        }
+        summary
+    }
+
+    /// Whether the task has used up its cpu time or wall-clock limit at `now`.
+    pub fn time_limit_exceeded(&self, now: usize) -> bool {
+        let cpu_time = self.user_time + self.kernel_time + (now - self.acct_time);
+        let wall_time = self.first_run_time.map_or(0, |first| now - first);
+        cpu_time >= self.cpu_limit || wall_time >= self.wall_limit
+    }
+
+    /// Charge the time since `sched_time` to the virtual runtime and real-time budget of this task.
+    pub fn update_runtime(&mut self, now: usize) {
+        let delta = now - self.sched_time;
+        self.vruntime += delta * NICE_0_WEIGHT / nice_to_weight(self.nice);
+        if let Some(dl) = self.dl.as_mut() {
+            dl.budget = dl.budget.saturating_sub(delta);
+        }
+        self.sched_time = now;
+    }
+}
+
+/// Statistics of a task returned by `sys_task_info`, times are in microseconds.
+#[derive(Copy, Clone)]
+#[repr(C)]
+pub struct TaskInfo {
+    pub user_time: usize,
+    pub kernel_time: usize,
+    pub switch_count: usize,
+    /// time since boot when the task was first scheduled
+    pub first_run_time: usize,
+    pub syscall_times: [u32; MAX_SYSCALL_NUM],
 }
 
 #[derive(Copy, Clone, PartialEq, Debug)]
@@ -12,6 +231,7 @@ pub enum TaskStatus {
     UnInit,
     Ready,
     Running,
+    Blocked,
     Exited,
 }
 
@@ -19,8 +239,8 @@ impl Display for TaskControlBlock {
     fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         write!(
             f,
-            "task_status: {:?}, task_context: {}",
-            self.task_status, self.task_cx
+            "task_status: {:?}, kthread: {}, pid: {:?}, tid: {}, exit_code: {}, nice: {}, vruntime: {}, dl: {:?}, task_context: {}",
+            self.task_status, self.kthread, self.pid, self.tid, self.exit_code, self.nice, self.vruntime, self.dl, self.task_cx
         )
     }
 }
@@ -1,9 +1,54 @@
-use crate::config::CLOCK_FREQ;
+use crate::config::{CLOCK_FREQ, MAX_HARTS};
+use crate::riscv::hart_id;
 use crate::sbi::set_timer;
+use crate::sync::SpinLock;
+use crate::task::wakeup_task;
+use alloc::collections::BinaryHeap;
+use alloc::vec::Vec;
+use core::cmp::Reverse;
+use lazy_static::lazy_static;
 use riscv::register::time;
 
 const TICKS_PER_SEC: usize = 100;
 const MSEC_PER_SEC: usize = 1000;
+const USEC_PER_SEC: usize = 1_000_000;
+const NSEC_PER_SEC: usize = 1_000_000_000;
+
+/// Kernel timers of tasks slept on a hart, together with the periodic scheduler tick of the hart.
+struct TimerQueue {
+    /// sleeping tasks ordered by (expire time, task id), earliest on top
+    timers: BinaryHeap<Reverse<(usize, usize)>>,
+    /// time of the next scheduler tick
+    next_tick: usize,
+}
+
+impl TimerQueue {
+    fn new() -> Self {
+        Self {
+            timers: BinaryHeap::new(),
+            next_tick: 0,
+        }
+    }
+
+    /// Program the sbi timer of current hart for the earlier one of next tick and the earliest sleeper.
+    fn program(&self) {
+        let next = match self.timers.peek() {
+            Some(Reverse((expire, _))) => self.next_tick.min(*expire),
+            None => self.next_tick,
+        };
+        set_timer(next);
+    }
+}
+
+lazy_static! {
+    /// timer queue of each hart, indexed by hart id
+    static ref TIMER_QUEUES: Vec<SpinLock<TimerQueue>> =
+        (0..MAX_HARTS).map(|_| SpinLock::new(TimerQueue::new())).collect();
+}
+
+fn local_timer_queue() -> &'static SpinLock<TimerQueue> {
+    &TIMER_QUEUES[hart_id()]
+}
 
 /// read the `mtime` register
 pub fn get_time() -> usize {
@@ -15,7 +60,65 @@ pub fn get_time_ms() -> usize {
     time::read() / (CLOCK_FREQ / MSEC_PER_SEC)
 }
 
-/// set the next timer interrupt
+/// convert microseconds into `mtime` cycles, `None` if it overflows
+pub fn us_to_time(us: usize) -> Option<usize> {
+    us.checked_mul(CLOCK_FREQ)
+        .map(|cycles| cycles / USEC_PER_SEC)
+}
+
+/// convert `mtime` cycles into microseconds
+pub fn time_to_us(time: usize) -> usize {
+    time * USEC_PER_SEC / CLOCK_FREQ
+}
+
+/// convert seconds and nanoseconds into `mtime` cycles, `None` if it overflows
+pub fn timespec_to_time(sec: usize, nsec: usize) -> Option<usize> {
+    sec.checked_mul(CLOCK_FREQ)?
+        .checked_add(nsec * (CLOCK_FREQ / 1000) / (NSEC_PER_SEC / 1000))
+}
+
+/// set the next timer interrupt of current hart
+///
+/// The tick only moves forward when it is due, a timer interrupt for a sleeper does not delay it.
 pub fn set_next_trigger() {
-    set_timer(get_time() + CLOCK_FREQ / TICKS_PER_SEC);
+    let mut queue = local_timer_queue().lock();
+    let now = get_time();
+    if queue.next_tick <= now {
+        queue.next_tick = now + CLOCK_FREQ / TICKS_PER_SEC;
+    }
+    queue.program();
+}
+
+/// Wake up task `task_id` at `expire`, the timer fires on current hart.
+pub fn add_timer(expire: usize, task_id: usize) {
+    let mut queue = local_timer_queue().lock();
+    queue.timers.push(Reverse((expire, task_id)));
+    queue.program();
+}
+
+/// Cancel the timer of task `task_id`, which may be on any hart.
+pub fn remove_timer(task_id: usize) {
+    for queue in TIMER_QUEUES.iter() {
+        let mut queue = queue.lock();
+        let timers = core::mem::take(&mut queue.timers);
+        queue.timers = timers
+            .into_iter()
+            .filter(|Reverse((_, id))| *id != task_id)
+            .collect();
+    }
+}
+
+/// Wake up all tasks whose timer on current hart has expired.
+pub fn check_timer() {
+    let now = get_time();
+    loop {
+        let mut queue = local_timer_queue().lock();
+        if !matches!(queue.timers.peek(), Some(Reverse((expire, _))) if *expire <= now) {
+            break;
+        }
+        let Reverse((_, task_id)) = queue.timers.pop().unwrap();
+        // waking up may touch the timer queue again
+        drop(queue);
+        wakeup_task(task_id);
+    }
 }
@@ -1,11 +1,15 @@
 use core::fmt::{Display, Formatter};
-use riscv::register::sstatus::{self, Sstatus, SPP};
+use riscv::register::sstatus::{self, Sstatus, FS, SPP};
 
-#[repr(C)]
+/// Saved on kernel stack by `__alltraps`, aligned to keep `sp` 16-byte aligned.
+#[derive(Copy, Clone)]
+#[repr(C, align(16))]
 pub struct TrapContext {
     pub x: [usize; 32],
     pub sstatus: Sstatus,
     pub sepc: usize,
+    /// `tp` of the hart which last returned to user mode with this context, i.e. its hart id
+    pub kernel_tp: usize,
 }
 
 impl TrapContext {
@@ -15,10 +19,13 @@ impl TrapContext {
     pub fn app_init_context(entry: usize, sp: usize) -> Self {
         let mut sstatus = sstatus::read();
         sstatus.set_spp(SPP::User);
+        // turned on by the first fp instruction, see `trap_handler`
+        sstatus.set_fs(FS::Off);
         let mut cx = Self {
             x: [0; 32],
             sstatus,
             sepc: entry,
+            kernel_tp: 0,
         };
         cx.set_sp(sp);
         cx
@@ -0,0 +1,151 @@
+//! Emulation of user instructions the hardware traps on but a user program may legally use.
+
+use super::TrapContext;
+use crate::mm::translate_user_addr;
+use riscv::register::scause::Exception;
+use riscv::register::time;
+
+const OPCODE_LOAD: u32 = 0x03;
+const OPCODE_STORE: u32 = 0x23;
+const OPCODE_SYSTEM: u32 = 0x73;
+
+const FUNCT3_CSRRS: u32 = 2;
+const CSR_TIME: u32 = 0xc01;
+
+/// Try to emulate the instruction at `sepc` which raised `exception` and step over it,
+/// return false if it is not supported, so the task should be signaled.
+pub fn emulate(exception: Exception, cx: &mut TrapContext, stval: usize) -> bool {
+    let (inst, len) = match fetch_instruction(cx.sepc) {
+        Some(fetched) => fetched,
+        None => return false,
+    };
+    let emulated = match exception {
+        Exception::LoadMisaligned | Exception::StoreMisaligned => {
+            emulate_misaligned(cx, inst, len, stval)
+        }
+        Exception::IllegalInstruction => emulate_illegal_instruction(cx, inst, len),
+        _ => false,
+    };
+    if emulated {
+        cx.sepc += len;
+    }
+    emulated
+}
+
+/// Read the instruction at user address `pc`, return it with its length, 2 if compressed.
+fn fetch_instruction(pc: usize) -> Option<(u32, usize)> {
+    let low = read_user_halfword(pc)? as u32;
+    if low & 0b11 != 0b11 {
+        return Some((low, 2));
+    }
+    let high = read_user_halfword(pc + 2)? as u32;
+    Some((low | high << 16, 4))
+}
+
+fn read_user_halfword(va: usize) -> Option<u16> {
+    Some(read_user_byte(va)? as u16 | (read_user_byte(va + 1)? as u16) << 8)
+}
+
+fn read_user_byte(va: usize) -> Option<u8> {
+    translate_user_addr(va, false).map(|pa| unsafe { *(pa as *const u8) })
+}
+
+fn write_user_byte(va: usize, value: u8) -> Option<()> {
+    translate_user_addr(va, true).map(|pa| unsafe { *(pa as *mut u8) = value })
+}
+
+/// A load or store of integer register `reg`, with access width in bytes.
+struct Access {
+    store: bool,
+    width: usize,
+    signed: bool,
+    reg: usize,
+}
+
+/// Decode integer loads and stores, including the compressed ones,
+/// fp loads and stores are not supported.
+fn decode_access(inst: u32, len: usize) -> Option<Access> {
+    let access = |store, width, signed, reg| {
+        Some(Access {
+            store,
+            width,
+            signed,
+            reg: reg as usize,
+        })
+    };
+    if len == 4 {
+        let funct3 = (inst >> 12) & 0b111;
+        return match inst & 0x7f {
+            OPCODE_LOAD => match funct3 {
+                0..=3 => access(false, 1 << funct3, true, (inst >> 7) & 0x1f),
+                4..=6 => access(false, 1 << (funct3 - 4), false, (inst >> 7) & 0x1f),
+                _ => None,
+            },
+            OPCODE_STORE if funct3 <= 3 => access(true, 1 << funct3, false, (inst >> 20) & 0x1f),
+            _ => None,
+        };
+    }
+    // compressed, registers are x8~x15 in the 3-bit fields
+    let funct3 = (inst >> 13) & 0b111;
+    let reg_short = ((inst >> 2) & 0b111) + 8;
+    match (inst & 0b11, funct3) {
+        (0b00, 0b010) => access(false, 4, true, reg_short), // c.lw
+        (0b00, 0b011) => access(false, 8, true, reg_short), // c.ld
+        (0b00, 0b110) => access(true, 4, false, reg_short), // c.sw
+        (0b00, 0b111) => access(true, 8, false, reg_short), // c.sd
+        (0b10, 0b010) => access(false, 4, true, (inst >> 7) & 0x1f), // c.lwsp
+        (0b10, 0b011) => access(false, 8, true, (inst >> 7) & 0x1f), // c.ldsp
+        (0b10, 0b110) => access(true, 4, false, (inst >> 2) & 0x1f), // c.swsp
+        (0b10, 0b111) => access(true, 8, false, (inst >> 2) & 0x1f), // c.sdsp
+        _ => None,
+    }
+}
+
+/// Perform a misaligned access to `addr` byte by byte.
+fn emulate_misaligned(cx: &mut TrapContext, inst: u32, len: usize, addr: usize) -> bool {
+    let access = match decode_access(inst, len) {
+        Some(access) => access,
+        None => return false,
+    };
+    if access.store {
+        let value = cx.x[access.reg];
+        (0..access.width).all(|i| write_user_byte(addr + i, (value >> (8 * i)) as u8).is_some())
+    } else {
+        let mut value = 0usize;
+        for i in 0..access.width {
+            match read_user_byte(addr + i) {
+                Some(byte) => value |= (byte as usize) << (8 * i),
+                None => return false,
+            }
+        }
+        if access.signed && access.width < 8 {
+            let shift = 64 - 8 * access.width;
+            value = (((value << shift) as isize) >> shift) as usize;
+        }
+        if access.reg != 0 {
+            cx.x[access.reg] = value;
+        }
+        true
+    }
+}
+
+/// Instructions some platforms leave to the kernel, currently `rdtime`.
+fn emulate_illegal_instruction(cx: &mut TrapContext, inst: u32, len: usize) -> bool {
+    if len != 4 || inst & 0x7f != OPCODE_SYSTEM {
+        return false;
+    }
+    let rd = ((inst >> 7) & 0x1f) as usize;
+    let funct3 = (inst >> 12) & 0b111;
+    let rs1 = (inst >> 15) & 0x1f;
+    let csr = inst >> 20;
+    match (funct3, rs1, csr) {
+        // rdtime rd, i.e. csrrs rd, time, x0
+        (FUNCT3_CSRRS, 0, CSR_TIME) => {
+            if rd != 0 {
+                cx.x[rd] = time::read();
+            }
+            true
+        }
+        _ => false,
+    }
+}
@@ -1,24 +1,55 @@
 mod context;
+mod emulate;
 
+use crate::drivers::handle_external_interrupt;
+use crate::riscv::hart_id;
+use crate::sbi::shutdown;
+use crate::stack_trace::print_stack_trace_from;
 use crate::syscall::syscall;
-use crate::task::{exit_current_and_run_next, suspend_current_and_run_next};
-use crate::timer::set_next_trigger;
+use crate::task::{
+    check_current_time_limit, current_should_preempt, current_task_id, current_trap_enter,
+    current_trap_exit, force_current_signal, handle_current_signals, suspend_current_and_run_next,
+    try_current_task_id, SIGBUS, SIGILL, SIGKILL, SIGSEGV, SIGTRAP,
+};
+use crate::timer::{check_timer, set_next_trigger};
 use core::arch::global_asm;
+use emulate::emulate;
 use riscv::register::{
     mtvec::TrapMode,
     scause::{self, Exception, Interrupt, Trap},
-    sie, stval, stvec,
+    sie,
+    sstatus::FS,
+    stval, stvec,
 };
 
 global_asm!(include_str!("trap.S"));
 
-/// initialize CSR `stvec` as the entry of `__alltraps`
+/// initialize CSR `stvec` as the entry of `__kernel_trap`, as the kernel is running,
+/// `__restore` switches it to `__alltraps` right before returning to user mode
 pub fn init() {
+    set_kernel_trap_entry();
+}
+
+fn set_kernel_trap_entry() {
     extern "C" {
-        fn __alltraps();
+        fn __kernel_trap();
+    }
+    unsafe {
+        stvec::write(__kernel_trap as usize, TrapMode::Direct);
+    }
+}
+
+/// software interrupt enabled, which other harts send as ipi
+pub fn enable_software_interrupt() {
+    unsafe {
+        sie::set_ssoft();
     }
+}
+
+/// external interrupt enabled, which the plic raises for device irqs
+pub fn enable_external_interrupt() {
     unsafe {
-        stvec::write(__alltraps as usize, TrapMode::Direct);
+        sie::set_sext();
     }
 }
 
@@ -32,35 +63,125 @@ pub fn enable_timer_interrupt() {
 #[no_mangle]
 /// handle an interrupt, exception, or system call from user space
 pub fn trap_handler(cx: &mut TrapContext) -> &mut TrapContext {
+    set_kernel_trap_entry();
+    current_trap_enter(cx);
     let scause = scause::read(); // get trap cause
     let stval = stval::read(); // get extra value
     match scause.cause() {
         Trap::Exception(Exception::UserEnvCall) => {
             cx.sepc += 4;
-            cx.x[10] = syscall(cx.x[17], [cx.x[10], cx.x[11], cx.x[12]]) as usize;
+            let args = [cx.x[10], cx.x[11], cx.x[12], cx.x[13], cx.x[14], cx.x[15]];
+            // `cx` is handed over, `sigreturn` rewrites it
+            cx.x[10] = syscall(cx.x[17], args, cx) as usize;
         }
-        Trap::Exception(Exception::StoreFault) | Trap::Exception(Exception::StorePageFault) => {
-            println!("[kernel] PageFault in application, bad addr = {:#x}, bad instruction = {:#x}, kernel killed it.", stval, cx.sepc);
-            exit_current_and_run_next();
+        Trap::Exception(exception) if emulate(exception, cx, stval) => {}
+        Trap::Exception(Exception::IllegalInstruction) if cx.sstatus.fs() == FS::Off => {
+            // first fp instruction of the task, turn fp on and run it again
+            cx.sstatus.set_fs(FS::Initial);
         }
-        Trap::Exception(Exception::IllegalInstruction) => {
-            println!("[kernel] IllegalInstruction in application, kernel killed it.");
-            exit_current_and_run_next();
+        Trap::Exception(exception) => {
+            let signum = exception_signal(exception);
+            println!(
+                "[kernel] {:?} in task {}, stval = {:#x}, sepc = {:#x}, sending signal {}.",
+                exception,
+                current_task_id(),
+                stval,
+                cx.sepc,
+                signum
+            );
+            force_current_signal(signum);
         }
         Trap::Interrupt(Interrupt::SupervisorTimer) => {
+            check_timer();
             set_next_trigger();
-            suspend_current_and_run_next();
+            check_current_time_limit();
+            if current_should_preempt() {
+                suspend_current_and_run_next();
+            }
         }
-        _ => {
+        Trap::Interrupt(Interrupt::SupervisorSoft) => {
+            // a real-time task has become ready, see `push_ready`
+            crate::riscv::clear_ssoft();
+            if current_should_preempt() {
+                suspend_current_and_run_next();
+            }
+        }
+        Trap::Interrupt(Interrupt::SupervisorExternal) => {
+            handle_external_interrupt();
+        }
+        Trap::Interrupt(interrupt) => {
             panic!(
-                "Unsupported trap {:?}, stval = {:#x}!",
-                scause.cause(),
-                stval
+                "Unsupported interrupt {:?}, stval = {:#x}!",
+                interrupt, stval
             );
         }
     }
+    trap_return(cx);
     cx
 }
 
+#[no_mangle]
+/// last step before current task returns to user mode with `cx`, also taken by a new task
+/// through `__enter_user`, so that its user time starts here as well
+pub fn trap_return(cx: &mut TrapContext) {
+    handle_current_signals(cx);
+    current_trap_exit(cx);
+}
+
+#[no_mangle]
+/// handle an interrupt or exception raised in supervisor mode,
+/// an exception is a kernel bug, so report it and shut down
+pub fn trap_from_kernel(cx: &mut TrapContext) {
+    let scause = scause::read();
+    let stval = stval::read();
+    match scause.cause() {
+        Trap::Interrupt(Interrupt::SupervisorTimer) => {
+            check_timer();
+            set_next_trigger();
+        }
+        Trap::Interrupt(Interrupt::SupervisorSoft) => {
+            crate::riscv::clear_ssoft();
+        }
+        Trap::Interrupt(Interrupt::SupervisorExternal) => {
+            handle_external_interrupt();
+        }
+        cause => {
+            println!(
+                "[kernel] {:?} in kernel on hart {}, scause = {:#x}, stval = {:#x}, sepc = {:#x}, sp = {:#x}",
+                cause,
+                hart_id(),
+                scause.bits(),
+                stval,
+                cx.sepc,
+                cx.x[2]
+            );
+            unsafe {
+                print_stack_trace_from(cx.x[8]);
+            }
+            // printed last, as it locks the task manager which the faulting code may hold
+            println!("[kernel] current task: {:?}", try_current_task_id());
+            shutdown(true)
+        }
+    }
+}
+
+/// Signal sent to the task raising a user exception other than `UserEnvCall`.
+fn exception_signal(exception: Exception) -> usize {
+    match exception {
+        Exception::InstructionFault
+        | Exception::InstructionPageFault
+        | Exception::LoadFault
+        | Exception::LoadPageFault
+        | Exception::StoreFault
+        | Exception::StorePageFault => SIGSEGV,
+        Exception::InstructionMisaligned
+        | Exception::LoadMisaligned
+        | Exception::StoreMisaligned => SIGBUS,
+        Exception::IllegalInstruction => SIGILL,
+        Exception::Breakpoint => SIGTRAP,
+        _ => SIGKILL,
+    }
+}
+
 use crate::println;
 pub use context::TrapContext;
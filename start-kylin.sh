@@ -2,6 +2,7 @@ cd kernel
 
 qemu-system-riscv64 \
   -machine virt \
+  -smp 4 \
   -nographic \
   -bios ../bootloader/rustsbi-qemu.bin \
   -device loader,file=target/riscv64gc-unknown-none-elf/release/os.bin,addr=0x80200000
\ No newline at end of file
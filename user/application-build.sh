@@ -7,9 +7,3 @@ python3 build.py
 #rust-objcopy --strip-all target/riscv64gc-unknown-none-elf/release/02power_7 -O binary target/riscv64gc-unknown-none-elf/release/02power_7.bin
 
 #rust-objcopy --strip-all target/riscv64gc-unknown-none-elf/release/03sleep -O binary target/riscv64gc-unknown-none-elf/release/03sleep.bin
-
-rust-objcopy --strip-all target/riscv64gc-unknown-none-elf/release/00write_a -O binary target/riscv64gc-unknown-none-elf/release/00write_a.bin
-
-rust-objcopy --strip-all target/riscv64gc-unknown-none-elf/release/01write_b -O binary target/riscv64gc-unknown-none-elf/release/01write_b.bin
-
-rust-objcopy --strip-all target/riscv64gc-unknown-none-elf/release/02write_c -O binary target/riscv64gc-unknown-none-elf/release/02write_c.bin
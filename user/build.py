@@ -1,25 +1,15 @@
 import os
 
-base_address = 0x80400000
-step = 0x20000
-linker = 'src/linker.ld'
+# apps are static PIE, the kernel relocates each one into its own slot
+target_dir = 'target/riscv64gc-unknown-none-elf/release/'
+musl_cc = os.environ.get('MUSL_CC', 'riscv64-linux-musl-gcc')
 
-app_id = 0
+os.system('cargo build --release')
 apps = os.listdir('src/bin')
 apps.sort()
 for app in apps:
-    app = app[:app.find('.')]
-    lines = []
-    lines_before = []
-    with open(linker, 'r') as f:
-        for line in f.readlines():
-            lines_before.append(line)
-            line = line.replace(hex(base_address), hex(base_address + step * app_id))
-            lines.append(line)
-    with open(linker, 'w+') as f:
-        f.writelines(lines)
-    os.system('cargo build --bin %s --release' % app)
-    print('[build.py] application %s start with address %s' % (app, hex(base_address + step * app_id)))
-    with open(linker, 'w+') as f:
-        f.writelines(lines_before)
-    app_id = app_id + 1
\ No newline at end of file
+    name, ext = os.path.splitext(app)
+    # C apps are linked with musl, the Rust ones are built by cargo above
+    if ext == '.c':
+        os.system('%s -static-pie -fPIE -O2 -o %s%s src/bin/%s' % (musl_cc, target_dir, name, app))
+    print('[build.py] application %s built' % name)
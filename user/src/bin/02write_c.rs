@@ -16,7 +16,7 @@ fn main() -> i32 {
             print!("C");
         }
         println!(" [{}/{}]", i + 1, HEIGHT);
-        yield_();
+        yield_().unwrap();
     }
     println!("Test write_c OK!");
     0
@@ -0,0 +1,44 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    brk, exit, mmap, setrlimit, thread_create, waittid, SysError, EXIT_RLIMIT, RLIMIT_AS,
+    RLIMIT_CPU, RLIM_INFINITY,
+};
+
+const PAGE_SIZE: usize = 0x1000;
+
+fn cpu_hog(_arg: usize) -> ! {
+    assert_eq!(setrlimit(RLIMIT_CPU, 10), Ok(0));
+    loop {}
+}
+
+fn quiet(_arg: usize) -> ! {
+    exit(7)
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    // heap and anonymous mappings are charged to RLIMIT_AS
+    assert_eq!(setrlimit(RLIMIT_AS, 2), Ok(0));
+    assert!(mmap(2 * PAGE_SIZE).unwrap() > 0);
+    assert_eq!(mmap(PAGE_SIZE), Err(SysError::ENOMEM));
+    let bottom = brk(0).unwrap() as usize;
+    assert_eq!(brk(bottom + PAGE_SIZE), Ok(bottom as isize));
+    assert_eq!(setrlimit(RLIMIT_AS, RLIM_INFINITY), Ok(0));
+    assert_eq!(brk(bottom + PAGE_SIZE), Ok((bottom + PAGE_SIZE) as isize));
+    assert!(mmap(PAGE_SIZE).unwrap() > 0);
+    println!("RLIMIT_AS OK");
+
+    // RLIMIT_CPU only kills the thread which exceeds it
+    let hog = thread_create(cpu_hog as usize, 0).unwrap();
+    let other = thread_create(quiet as usize, 0).unwrap();
+    assert_eq!(waittid(hog as usize), Ok(EXIT_RLIMIT));
+    assert_eq!(waittid(other as usize), Ok(7));
+    println!("RLIMIT_CPU OK");
+    println!("Test rlimit OK!");
+    0
+}
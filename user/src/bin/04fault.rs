@@ -0,0 +1,57 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::arch::asm;
+use user_lib::{exit, thread_create, waittid, EXIT_BUS, EXIT_SEGV, EXIT_TRAP};
+
+/// nothing is mapped there on the QEMU virt machine
+const UNMAPPED: usize = 0x800;
+
+static WORDS: [u32; 2] = [0x4433_2211, 0x8877_6655];
+
+fn load_fault(_arg: usize) -> ! {
+    unsafe { asm!("lb {0}, 0({1})", out(reg) _, in(reg) UNMAPPED) };
+    exit(0)
+}
+
+fn misaligned_load(_arg: usize) -> ! {
+    let value: u32;
+    let addr = WORDS.as_ptr() as usize + 1;
+    unsafe { asm!("lwu {0}, 0({1})", out(reg) value, in(reg) addr) };
+    exit(if value == 0x5544_3322 { 0 } else { 1 })
+}
+
+fn misaligned_fault(_arg: usize) -> ! {
+    unsafe { asm!("lw {0}, 0({1})", out(reg) _, in(reg) UNMAPPED + 1) };
+    exit(0)
+}
+
+fn breakpoint(_arg: usize) -> ! {
+    unsafe { asm!("ebreak") };
+    exit(0)
+}
+
+/// Run `entry` in a thread and return its exit code.
+fn run(entry: fn(usize) -> !) -> i32 {
+    let tid = thread_create(entry as usize, 0).unwrap();
+    waittid(tid as usize).unwrap()
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    assert_eq!(run(load_fault), EXIT_SEGV);
+    println!("load fault OK");
+    // emulated by the kernel if the hardware does not support it
+    assert_eq!(run(misaligned_load), 0);
+    // the hardware may report the fault or the misalignment first
+    let code = run(misaligned_fault);
+    assert!(code == EXIT_SEGV || code == EXIT_BUS);
+    println!("misaligned access OK");
+    assert_eq!(run(breakpoint), EXIT_TRAP);
+    println!("ebreak OK");
+    println!("Test fault OK!");
+    0
+}
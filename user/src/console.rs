@@ -1,13 +1,14 @@
-use crate::write;
+use crate::{read, write, SysError};
 use core::fmt::{Arguments, Write};
 
+const STDIN: usize = 0;
 const STDOUT: usize = 1;
 
 struct Stdout;
 
 impl Write for Stdout {
     fn write_str(&mut self, s: &str) -> core::fmt::Result {
-        write(STDOUT, s.as_bytes());
+        write(STDOUT, s.as_bytes()).map_err(|_| core::fmt::Error)?;
         Ok(())
     }
 }
@@ -16,6 +17,14 @@ pub fn print(args: Arguments) {
     Stdout.write_fmt(args).unwrap();
 }
 
+/// Read a character from stdin, block until one is typed.
+pub fn getchar() -> u8 {
+    let mut c = [0u8; 1];
+    // read again if a signal interrupts the wait
+    while read(STDIN, &mut c) == Err(SysError::EINTR) {}
+    c[0]
+}
+
 #[macro_export]
 macro_rules! print {
     ($fmt: literal $(, $($arg: tt)+)?) => {
@@ -5,6 +5,7 @@
 #[macro_use]
 pub mod console;
 mod lang_items;
+pub mod sync;
 mod syscall;
 
 #[no_mangle]
@@ -12,7 +13,6 @@ mod syscall;
 pub extern "C" fn _start() -> ! {
     clear_bss();
     exit(main());
-    panic!("unreachable after sys_exit!");
 }
 
 #[linkage = "weak"]
@@ -31,17 +31,277 @@ fn clear_bss() {
     });
 }
 
+use core::sync::atomic::AtomicU32;
 use syscall::*;
 
-pub fn write(fd: usize, buf: &[u8]) -> isize {
-    sys_write(fd, buf)
+pub const MAX_SYSCALL_NUM: usize = 1100;
+
+pub const FUTEX_WAIT: usize = 0;
+pub const FUTEX_WAKE: usize = 1;
+
+/// Error of a syscall, which returns it as `-errno` like Linux.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SysError {
+    EPERM = 1,
+    ENOENT = 2,
+    ESRCH = 3,
+    EINTR = 4,
+    EBADF = 9,
+    ECHILD = 10,
+    EAGAIN = 11,
+    ENOMEM = 12,
+    EFAULT = 14,
+    EBUSY = 16,
+    EINVAL = 22,
+    EDEADLK = 35,
+    ENOSYS = 38,
+}
+
+impl SysError {
+    /// Decode the return value of a syscall, `Err` if it is `-errno`.
+    pub fn check(ret: isize) -> Result<isize, SysError> {
+        let error = match -ret {
+            1 => SysError::EPERM,
+            2 => SysError::ENOENT,
+            3 => SysError::ESRCH,
+            4 => SysError::EINTR,
+            9 => SysError::EBADF,
+            10 => SysError::ECHILD,
+            11 => SysError::EAGAIN,
+            12 => SysError::ENOMEM,
+            14 => SysError::EFAULT,
+            16 => SysError::EBUSY,
+            22 => SysError::EINVAL,
+            35 => SysError::EDEADLK,
+            38 => SysError::ENOSYS,
+            _ => return Ok(ret),
+        };
+        Err(error)
+    }
+}
+
+/// syscall classes for `trace`
+pub const TRACE_FS: usize = 1 << 0;
+pub const TRACE_PROCESS: usize = 1 << 1;
+pub const TRACE_SIGNAL: usize = 1 << 2;
+pub const TRACE_SYNC: usize = 1 << 3;
+pub const TRACE_THREAD: usize = 1 << 4;
+pub const TRACE_ALL: usize = (1 << 5) - 1;
+
+/// cpu time limit in milliseconds
+pub const RLIMIT_CPU: usize = 0;
+/// max pages of heap, anonymous mappings and page frames of the process
+pub const RLIMIT_AS: usize = 9;
+/// wall-clock limit since the first run in milliseconds
+pub const RLIMIT_WALL: usize = 16;
+pub const RLIM_INFINITY: usize = usize::MAX;
+/// exit code of a task killed by the default action of `SIGSEGV`
+pub const EXIT_SEGV: i32 = -(SIGSEGV as i32);
+/// exit code of a task killed by the default action of `SIGILL`
+pub const EXIT_ILL: i32 = -(SIGILL as i32);
+/// exit code of a task killed by the default action of `SIGTRAP`
+pub const EXIT_TRAP: i32 = -(SIGTRAP as i32);
+/// exit code of a task killed by the default action of `SIGBUS`
+pub const EXIT_BUS: i32 = -(SIGBUS as i32);
+/// exit code of a task killed for exceeding its time limit
+pub const EXIT_RLIMIT: i32 = -(SIGXCPU as i32);
+
+#[repr(C)]
+pub struct TimeSpec {
+    pub sec: usize,
+    pub nsec: usize,
+}
+
+/// Statistics of current task, times are in microseconds.
+#[repr(C)]
+pub struct TaskInfo {
+    pub user_time: usize,
+    pub kernel_time: usize,
+    pub switch_count: usize,
+    /// time since boot when the task was first scheduled
+    pub first_run_time: usize,
+    pub syscall_times: [u32; MAX_SYSCALL_NUM],
+}
+
+impl TaskInfo {
+    pub fn new() -> Self {
+        Self {
+            user_time: 0,
+            kernel_time: 0,
+            switch_count: 0,
+            first_run_time: 0,
+            syscall_times: [0; MAX_SYSCALL_NUM],
+        }
+    }
+}
+
+pub const SIGHUP: usize = 1;
+pub const SIGINT: usize = 2;
+pub const SIGQUIT: usize = 3;
+pub const SIGILL: usize = 4;
+pub const SIGTRAP: usize = 5;
+pub const SIGABRT: usize = 6;
+pub const SIGBUS: usize = 7;
+pub const SIGKILL: usize = 9;
+pub const SIGUSR1: usize = 10;
+pub const SIGSEGV: usize = 11;
+pub const SIGUSR2: usize = 12;
+pub const SIGALRM: usize = 14;
+pub const SIGTERM: usize = 15;
+pub const SIGCHLD: usize = 17;
+pub const SIGSTOP: usize = 19;
+pub const SIGXCPU: usize = 24;
+
+pub const SIG_DFL: usize = 0;
+pub const SIG_IGN: usize = 1;
+
+/// Action of a signal, `mask` has bit `n` set for each signal `n` blocked while `handler` runs.
+///
+/// `handler` is called with the signal number and must end with `sigreturn`.
+#[repr(C)]
+#[derive(Copy, Clone, Default)]
+pub struct SignalAction {
+    pub handler: usize,
+    pub mask: u32,
+}
+
+/// Open `fd` again at the lowest free fd and return the new fd.
+pub fn dup(fd: usize) -> Result<isize, SysError> {
+    SysError::check(sys_dup(fd))
+}
+pub fn close(fd: usize) -> Result<isize, SysError> {
+    SysError::check(sys_close(fd))
+}
+pub fn read(fd: usize, buf: &mut [u8]) -> Result<isize, SysError> {
+    SysError::check(sys_read(fd, buf))
+}
+pub fn write(fd: usize, buf: &[u8]) -> Result<isize, SysError> {
+    SysError::check(sys_write(fd, buf))
+}
+pub fn exit(exit_code: i32) -> ! {
+    sys_exit(exit_code);
+    panic!("unreachable after sys_exit!");
+}
+pub fn yield_() -> Result<isize, SysError> {
+    SysError::check(sys_yield())
+}
+pub fn set_priority(nice: isize) -> Result<isize, SysError> {
+    SysError::check(sys_set_priority(nice))
+}
+/// Limit a resource of current task, see `RLIMIT_*`.
+pub fn setrlimit(resource: usize, limit: usize) -> Result<isize, SysError> {
+    SysError::check(sys_setrlimit(resource, limit))
+}
+pub fn get_time() -> Result<isize, SysError> {
+    SysError::check(sys_get_time())
+}
+/// Move the program break to `addr` and return the new break, 0 to query it.
+pub fn brk(addr: usize) -> Result<isize, SysError> {
+    SysError::check(sys_brk(addr))
+}
+/// Map `len` bytes of zeroed anonymous memory at an address chosen by the kernel.
+pub fn mmap(len: usize) -> Result<isize, SysError> {
+    const PROT_READ_WRITE: usize = 0x3;
+    const MAP_PRIVATE_ANONYMOUS: usize = 0x22;
+    SysError::check(sys_mmap(
+        0,
+        len,
+        PROT_READ_WRITE,
+        MAP_PRIVATE_ANONYMOUS,
+        usize::MAX,
+        0,
+    ))
+}
+pub fn sleep(ms: usize) -> Result<isize, SysError> {
+    SysError::check(sys_nanosleep(&TimeSpec {
+        sec: ms / 1000,
+        nsec: ms % 1000 * 1_000_000,
+    }))
+}
+/// Become periodic real-time with parameters in microseconds, `yield_` ends the job of a period.
+pub fn sched_setattr(runtime: usize, deadline: usize, period: usize) -> Result<isize, SysError> {
+    SysError::check(sys_sched_setattr(runtime, deadline, period))
+}
+pub fn task_info(info: &mut TaskInfo) -> Result<isize, SysError> {
+    SysError::check(sys_task_info(info))
+}
+/// Create a thread running `entry(arg)`, which must end with `exit`.
+pub fn thread_create(entry: usize, arg: usize) -> Result<isize, SysError> {
+    SysError::check(sys_thread_create(entry, arg))
+}
+pub fn gettid() -> Result<isize, SysError> {
+    SysError::check(sys_gettid())
+}
+/// Wait until thread `tid` exits and return its exit code, `ECHILD` if there is no such thread.
+pub fn waittid(tid: usize) -> Result<i32, SysError> {
+    let mut exit_code = 0;
+    SysError::check(sys_waittid(tid, &mut exit_code))?;
+    Ok(exit_code)
+}
+pub fn kill(pid: usize, signum: usize) -> Result<isize, SysError> {
+    SysError::check(sys_kill(pid, signum))
+}
+pub fn sigaction(
+    signum: usize,
+    action: Option<&SignalAction>,
+    old_action: Option<&mut SignalAction>,
+) -> Result<isize, SysError> {
+    SysError::check(sys_sigaction(signum, action, old_action))
+}
+/// Set blocked signals, bit `n` for signal `n`, and return the old mask.
+pub fn sigprocmask(mask: u32) -> Result<isize, SysError> {
+    SysError::check(sys_sigprocmask(mask))
+}
+/// Return from a signal handler to the context the signal interrupted.
+pub fn sigreturn() -> ! {
+    sys_sigreturn();
+    panic!("unreachable after sys_sigreturn!");
+}
+pub fn mutex_create() -> Result<isize, SysError> {
+    SysError::check(sys_mutex_create())
+}
+pub fn mutex_lock(mutex_id: usize) -> Result<isize, SysError> {
+    SysError::check(sys_mutex_lock(mutex_id))
+}
+pub fn mutex_unlock(mutex_id: usize) -> Result<isize, SysError> {
+    SysError::check(sys_mutex_unlock(mutex_id))
+}
+pub fn semaphore_create(res_count: usize) -> Result<isize, SysError> {
+    SysError::check(sys_semaphore_create(res_count))
+}
+pub fn semaphore_up(sem_id: usize) -> Result<isize, SysError> {
+    SysError::check(sys_semaphore_up(sem_id))
+}
+pub fn semaphore_down(sem_id: usize) -> Result<isize, SysError> {
+    SysError::check(sys_semaphore_down(sem_id))
+}
+pub fn condvar_create() -> Result<isize, SysError> {
+    SysError::check(sys_condvar_create())
+}
+pub fn condvar_signal(condvar_id: usize) -> Result<isize, SysError> {
+    SysError::check(sys_condvar_signal(condvar_id))
+}
+/// Wake up all threads waiting on `condvar_id`.
+pub fn condvar_broadcast(condvar_id: usize) -> Result<isize, SysError> {
+    SysError::check(sys_condvar_broadcast(condvar_id))
+}
+/// Release `mutex_id`, wait for a signal on `condvar_id`, then lock `mutex_id` again.
+pub fn condvar_wait(condvar_id: usize, mutex_id: usize) -> Result<isize, SysError> {
+    SysError::check(sys_condvar_wait(condvar_id, mutex_id))
+}
+/// Log syscalls of classes in `mask` made by process `pid` to the kernel log, 0 stops it.
+pub fn trace(pid: usize, mask: usize) -> Result<isize, SysError> {
+    SysError::check(sys_trace(pid, mask))
 }
-pub fn exit(exit_code: i32) -> isize {
-    sys_exit(exit_code)
+/// Refuse `mutex_lock` and `semaphore_down` which may deadlock with `EDEADLK` if `enabled`.
+pub fn enable_deadlock_detect(enabled: bool) -> Result<isize, SysError> {
+    SysError::check(sys_enable_deadlock_detect(enabled as usize))
 }
-pub fn yield_() -> isize {
-    sys_yield()
+/// Block until woken by `futex_wake` if `uaddr` still holds `val`, `EAGAIN` if it does not.
+pub fn futex_wait(uaddr: &AtomicU32, val: u32) -> Result<isize, SysError> {
+    SysError::check(sys_futex(uaddr, FUTEX_WAIT, val as usize))
 }
-pub fn get_time() -> isize {
-    sys_get_time()
+/// Wake up at most `count` threads waiting on `uaddr`, return how many were woken.
+pub fn futex_wake(uaddr: &AtomicU32, count: usize) -> Result<isize, SysError> {
+    SysError::check(sys_futex(uaddr, FUTEX_WAKE, count))
 }
@@ -0,0 +1,40 @@
+use crate::{futex_wait, futex_wake};
+use core::sync::atomic::{AtomicU32, Ordering};
+
+const UNLOCKED: u32 = 0;
+const LOCKED: u32 = 1;
+/// locked and some thread may be waiting in the kernel
+const CONTENDED: u32 = 2;
+
+/// Mutex on a futex word, which only enters the kernel when threads contend for it.
+pub struct FutexMutex {
+    state: AtomicU32,
+}
+
+impl FutexMutex {
+    pub const fn new() -> Self {
+        Self {
+            state: AtomicU32::new(UNLOCKED),
+        }
+    }
+
+    pub fn lock(&self) {
+        if self
+            .state
+            .compare_exchange(UNLOCKED, LOCKED, Ordering::Acquire, Ordering::Relaxed)
+            .is_ok()
+        {
+            return;
+        }
+        while self.state.swap(CONTENDED, Ordering::Acquire) != UNLOCKED {
+            // `EAGAIN` or `EINTR` only mean the state is checked again
+            let _ = futex_wait(&self.state, CONTENDED);
+        }
+    }
+
+    pub fn unlock(&self) {
+        if self.state.swap(UNLOCKED, Ordering::Release) == CONTENDED {
+            futex_wake(&self.state, 1).unwrap();
+        }
+    }
+}